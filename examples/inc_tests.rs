@@ -4,9 +4,8 @@ extern crate presort;
 
 mod inc_tree;
 
-//use presort::{PresortedVec, PermutedVec};
+use presort::PresortedVec;
 use inc_tree::{Tree, IncTree, dump, update, update_no_pad};
-use inc_tree::sortvec::IntoSortedIterator;
 
 fn main(){
     test_tree();
@@ -16,9 +15,9 @@ fn main(){
 // #[test]
 fn test_tree() {
     let main_tree = Tree::new_node(37);
-    let mut vec = Vec::new();
+    let mut vec = PresortedVec::new();
     dump(&main_tree, 0, &mut vec);
-    assert_eq!(vec.into_sorted_iter().collect::<Vec<&usize>>(), vec![&37]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&37]);
 }
 
 // #[test]
@@ -36,17 +35,17 @@ fn test_update() {
     main_tree.push_child(Tree::new_node(42));
     main_tree.push_child(Tree::new_node(20));
     main_tree.push_child(Tree::new_node(63));
-    let mut vec = Vec::new();
+    let mut vec = PresortedVec::new();
     dump(&main_tree, 0, &mut vec);
     println!("finished first dump");
-    assert_eq!(vec.into_sorted_iter().collect::<Vec<&usize>>(), vec![&20,&37,&42,&63]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&20,&37,&42,&63]);
     println!("success");
 
     println!("start first update");
     main_tree.get_child(1).set_data(25);
     update(&main_tree, 0, &mut vec);
     println!("finished first update");
-    assert_eq!(vec.into_sorted_iter().collect::<Vec<&usize>>(), vec![&25,&37,&42,&63]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&25,&37,&42,&63]);
     println!("success");
 
     println!("start add branch");
@@ -58,7 +57,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished add branch");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&25,&37,&42,&47,&53,&57,&61,&63]
     );
     println!("success");
@@ -68,7 +67,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished update branch");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&25,&37,&42,&47,&53,&57,&63,&77]
     );
     println!("success");
@@ -79,7 +78,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished early insertion");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&1,&25,&37,&42,&47,&53,&57,&63,&77]
     );
     println!("success");
@@ -92,7 +91,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished deep add");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&1,&25,&37,&42,&47,&53,&57,&63,&77,&100,&101]
     );
     println!("success");
@@ -103,7 +102,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished deep edit");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&1,&25,&37,&42,&47,&53,&57,&63,&77,&100,&105]
     );
     println!("success");
@@ -114,7 +113,7 @@ fn test_update() {
     update(&main_tree, 0, &mut vec);
     println!("finished pop branch");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&0,&0,&1,&25,&37,&42,&47,&53,&57,&63,&77]
     );
     println!("success");
@@ -124,7 +123,7 @@ fn test_update() {
     update_no_pad(&main_tree, 0, &mut vec);
     println!("finished remove branch");
     assert_eq!(
-        vec.into_sorted_iter().collect::<Vec<&usize>>(),
+        vec.sorted_iter().collect::<Vec<&usize>>(),
         vec![&37,&42,&47,&53,&57,&63,&77]
     );
     println!("success");
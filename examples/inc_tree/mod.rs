@@ -1,11 +1,11 @@
 // TODO: use Weak<> pointers to avoid memory leaks
-extern crate presort;
+#![allow(dead_code)]
 
-pub mod sortvec;
+extern crate presort;
 
 use std::rc::Rc;
 use std::cell::RefCell; 
-use inc_tree::sortvec::SortVec;
+use presort::SortVec;
 
 pub struct TreeNode<T> {
     // whether this node's data has changed
@@ -46,7 +46,7 @@ impl<T: PartialEq + Clone> IncTree<T> for Tree<T> {
             needs_update: true,
             vec_index: 0,
             parent: None,
-            data: data,
+            data,
             children: vec![]
         }))
     }
@@ -158,7 +158,7 @@ pub fn dump<T: Ord+Clone, V: SortVec<T>>(tree: &Tree<T>, free_index: usize, vec:
     vec.push(tree.data.clone());
     tree.dirty_val = false;
     let mut next_free_index = free_index + 1;
-    for kid in &tree.children { next_free_index = dump(&kid, next_free_index, vec); }
+    for kid in &tree.children { next_free_index = dump(kid, next_free_index, vec); }
     tree.needs_update = false;
     next_free_index
 }
@@ -194,7 +194,7 @@ pub fn update<T: Ord+Clone+Default, V: SortVec<T>>(tree: &Tree<T>, free_index: u
         tree.dirty_val = false;
         // update kids
         for kid in &tree.children {
-            next_free_index = update(&kid, next_free_index, vec);
+            next_free_index = update(kid, next_free_index, vec);
         }
         tree.needs_update = false;
     } else {
@@ -240,7 +240,7 @@ fn update_no_pad_internal<T: Ord+Clone, V: SortVec<T>>(tree: &Tree<T>, free_inde
         tree.dirty_val = false;
         // update kids
         for kid in &tree.children {
-            next_free_index = update_no_pad_internal(&kid, next_free_index, vec);
+            next_free_index = update_no_pad_internal(kid, next_free_index, vec);
         }
         tree.needs_update = false;
     } else {
@@ -12,10 +12,10 @@ use std::io::Write;
 use rand::Rng;
 use time::Duration;
 use clap::{App, Arg};
-use presort::{PresortedVec, PermutedVec, MergeVec};
+use presort::{PresortedVec, PermutedVec, MergeVec, AdaptiveSortVec};
 use stats::{mean,stddev};
 use inc_tree::{Tree, IncTree, dump, update, update_no_pad};
-use inc_tree::sortvec::SortVec;
+use presort::SortVec;
 
 const ADD_SIZE: usize = 5;
 
@@ -50,6 +50,10 @@ fn main() {
         .long("merge_pad")
         .help("Use the permuted vector implementation with padding")
         .conflicts_with_all(&["vec","presort","persort_pad","permute","permute_pad","merge"])
+    ).arg(Arg::with_name("adaptive")
+        .long("adaptive")
+        .help("Use the adaptive vector implementation")
+        .conflicts_with_all(&["vec","presort","presort_pad","permute","permute_pad","merge","merge_pad"])
     ).args_from_usage("\
         --tag [tag]                                 'max depth of initial tree'
         [data_size] -b [data_size]                  'data size in bytes (unused)'
@@ -75,11 +79,10 @@ fn main() {
     let a = value_t!(args.value_of("add"), f32).unwrap_or(0.5);
     let c = value_t!(args.value_of("change"), f32).unwrap_or(0.5);
     let t = value_t!(args.value_of("trials"), usize).unwrap_or(1);
-    let mut o: Box<Write> = if let Some(f) = args.value_of("outfile") {
+    let mut o: Box<dyn Write> = if let Some(f) = args.value_of("outfile") {
         Box::new(
             OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
             .open(f)
             .unwrap()
@@ -88,23 +91,7 @@ fn main() {
     
     //write out header
     if args.is_present("header"){
-        writeln!(o, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-            "timestamp",
-            "user_tag",
-            "vec_type",
-            "data_size",
-            "depth",
-            "nodes",
-            "edits",
-            "chance_shape",
-            "chance_add",
-            "chance_reorder",
-            "time_dump", "sd_dump",
-            "time_init_sort", "sd_init_sort",
-            "time_modification", "sd_modification",
-            "time_update", "sd_update",
-            "time_sort", "sd_sort"
-        ).unwrap();
+        writeln!(o, "timestamp\tuser_tag\tvec_type\tdata_size\tdepth\tnodes\tedits\tchance_shape\tchance_add\tchance_reorder\ttime_dump\tsd_dump\ttime_init_sort\tsd_init_sort\ttime_modification\tsd_modification\ttime_update\tsd_update\ttime_sort\tsd_sort").unwrap();
     }
     if t == 0 {return}
 
@@ -132,6 +119,8 @@ fn main() {
                 VecVersion::Merge(MergeVec::new())
             } else if args.is_present("merge_pad") {
                 VecVersion::MerPad(MergeVec::new())
+            } else if args.is_present("adaptive") {
+                VecVersion::Adaptive(AdaptiveSortVec::new())
             } else {
                 VecVersion::Vec(Vec::new())
             };
@@ -149,6 +138,7 @@ fn main() {
                 VecVersion::PerPad(ref mut v) => dump(&tree, 0, v),
                 VecVersion::Merge(ref mut v) => dump(&tree, 0, v),
                 VecVersion::MerPad(ref mut v) => dump(&tree, 0, v),
+                VecVersion::Adaptive(ref mut v) => dump(&tree, 0, v),
             };
         }).num_nanoseconds().unwrap());
 
@@ -164,6 +154,7 @@ fn main() {
                 VecVersion::PerPad(ref mut v) => v.sort(),
                 VecVersion::Merge(ref mut v) => v.sort(),
                 VecVersion::MerPad(ref mut v) => v.sort(),
+                VecVersion::Adaptive(ref mut v) => v.sort(),
             };
         }).num_nanoseconds().unwrap());
 
@@ -201,6 +192,7 @@ fn main() {
                 VecVersion::PerPad(ref mut v) => update(&tree, 0, v),
                 VecVersion::Merge(ref mut v) => update_no_pad(&tree, 0, v),
                 VecVersion::MerPad(ref mut v) => update(&tree, 0, v),
+                VecVersion::Adaptive(ref mut v) => update_no_pad(&tree, 0, v),
             };
         }).num_nanoseconds().unwrap());
 
@@ -216,6 +208,7 @@ fn main() {
                 VecVersion::PerPad(ref mut v) => v.sort(),
                 VecVersion::Merge(ref mut v) => v.sort(),
                 VecVersion::MerPad(ref mut v) => v.sort(),
+                VecVersion::Adaptive(ref mut v) => v.sort(),
             };
         }).num_nanoseconds().unwrap());
 
@@ -232,6 +225,7 @@ fn main() {
         else if args.is_present("permute_pad") {"permute_pad"}
         else if args.is_present("merge") {"merge"}
         else if args.is_present("merge_pad") {"merge_pad"}
+        else if args.is_present("adaptive") {"adaptive"}
         else {"vec"};
 
     writeln!(o, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}\t{:.5}",
@@ -255,6 +249,7 @@ enum VecVersion<T: Ord + Clone> {
     PerPad(PermutedVec<T>),
     Merge(MergeVec<T>),
     MerPad(MergeVec<T>),
+    Adaptive(AdaptiveSortVec<T>),
 }
 
 //creates a random tree with the requested parameters
@@ -269,7 +264,7 @@ fn build_tree(max_depth: usize, nodes: usize) -> Tree<usize> {
         let depth = rng.gen::<usize>() % max_depth;
         for _ in 0..depth {
             let num = branch.num_children();
-            if num <= 0 {break};
+            if num == 0 {break};
             branch = branch.get_child(rng.gen::<usize>() % num);
         }
         branch.push_child(Tree::new_node(rng.gen()));
@@ -284,10 +279,10 @@ fn random_subtree(tree: &Tree<usize>, high_depth: usize) -> Tree<usize> {
     let depth = rng.gen::<usize>() % high_depth;
     for _ in 0..depth {
         let num = subtree.num_children();
-        if num <= 0 { return subtree; }
+        if num == 0 { return subtree; }
         subtree = subtree.get_child(rng.gen::<usize>() % num);
     }
-    return subtree;
+    subtree
 }
 
 fn mutate_vals(tree: &Tree<usize>, high_depth: usize, edits: usize) {
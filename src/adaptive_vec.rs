@@ -0,0 +1,254 @@
+use compare::Natural;
+use index::Index;
+use permuted_vec::PermutedIndexedIter;
+use presorted_vec::{PresortedIter, PresortedRefMut, PresortedVec};
+#[cfg(feature = "stats")]
+use stats::Stats;
+
+pub use permuted_vec::SortStrategy;
+
+/// Counts of the edits made to an adaptive vector since it was last sorted.
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub struct EditStats {
+    /// The number of elements which may be out of order.
+    pub displaced: usize,
+    /// The number of elements pushed.
+    pub pushes: usize,
+    /// The number of elements set.
+    pub sets: usize,
+    /// The number of truncations.
+    pub truncations: usize,
+}
+
+/// The type of adaptive vectors.
+///
+/// An adaptive vector tracks displaced elements like a `PresortedVec`,
+/// but at each sort it chooses a strategy from the edits made since the last sort:
+/// a few displaced elements are repaired by binary insertion,
+/// elements pushed in order are merged as runs, and anything else gets a full sort.
+#[derive(Clone,Debug)]
+pub struct AdaptiveSortVec<T, Ix = usize> {
+    // The underlying presorted vector
+    vec: PresortedVec<T, Natural, Ix>,
+    // The edits since the last sort
+    stats: EditStats,
+    // The strategy used by the last sort which had displaced elements
+    last_strategy: Option<SortStrategy>,
+}
+
+impl<T> AdaptiveSortVec<T> where T: Ord {
+    /// Create a new, empty adaptive vector.
+    pub fn new() -> AdaptiveSortVec<T> {
+        AdaptiveSortVec::new_in()
+    }
+}
+
+impl<T, Ix> AdaptiveSortVec<T, Ix> where T: Ord, Ix: Index {
+    /// Create a new, empty adaptive vector,
+    /// for any index type, for example `AdaptiveSortVec::<T, u32>::new_in()`.
+    pub fn new_in() -> AdaptiveSortVec<T, Ix> {
+        AdaptiveSortVec {
+            vec: PresortedVec::new_in(),
+            stats: EditStats::default(),
+            last_strategy: None,
+        }
+    }
+
+    /// The length of the vector.
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Append an element to the end of the vector.
+    pub fn push(&mut self, value: T) {
+        self.stats.pushes += 1;
+        self.vec.push(value);
+    }
+
+    /// Set the `i`th element of the vector.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.stats.sets += 1;
+        self.vec.set(index, value);
+    }
+
+    /// Set many elements of the vector at once.
+    /// The edited elements are not compared with their neighbours until the next sort.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        let stats = &mut self.stats;
+        self.vec.apply_edits(edits.into_iter().inspect(|_| stats.sets += 1));
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// The appended elements are not compared with their neighbours until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        let stats = &mut self.stats;
+        self.vec.extend_edits(values.into_iter().inspect(|_| stats.pushes += 1));
+    }
+
+    /// Truncate this vector.
+    /// This does not change whether the vector is sorted.
+    pub fn truncate(&mut self, len: usize) {
+        self.stats.truncations += 1;
+        self.vec.truncate(len);
+    }
+
+    /// Sort the vector, using the strategy chosen by `choose_strategy`.
+    pub fn sort(&mut self) {
+        if self.vec.displaced_count() > 0 {
+            let strategy = self.choose_strategy();
+            self.sort_with(strategy);
+        } else {
+            self.stats = EditStats::default();
+        }
+    }
+
+    /// Sort the vector using the given strategy.
+    pub fn sort_with(&mut self, strategy: SortStrategy) {
+        if self.vec.displaced_count() > 0 {
+            self.vec.sort_with(strategy);
+            self.last_strategy = Some(strategy);
+        }
+        self.stats = EditStats::default();
+    }
+
+    /// The strategy the next sort will use, based on the edits since the last sort.
+    ///
+    /// If binary insertion of the displaced elements takes fewer comparisons
+    /// than a linear scan, they are repaired. Otherwise if elements have mostly been pushed,
+    /// for example refilling a truncated vector, they are likely to form runs,
+    /// so the runs are merged. Otherwise the vector is fully sorted.
+    pub fn choose_strategy(&self) -> SortStrategy {
+        let len = self.len();
+        let displaced = self.vec.displaced_count();
+        let log_len = (usize::BITS - len.leading_zeros()) as usize;
+        if displaced * log_len <= len {
+            SortStrategy::Repair
+        } else if self.stats.pushes > self.stats.sets || self.stats.truncations > 0 {
+            SortStrategy::Merge
+        } else {
+            SortStrategy::Full
+        }
+    }
+
+    /// The edits made since the last sort.
+    pub fn edit_stats(&self) -> EditStats {
+        EditStats {
+            displaced: self.vec.displaced_count(),
+            .. self.stats
+        }
+    }
+
+    /// The strategy used by the last sort which had displaced elements to sort.
+    pub fn last_strategy(&self) -> Option<SortStrategy> {
+        self.last_strategy
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.vec.stats()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.vec.reset_stats();
+    }
+
+    /// Is the vector already sorted?
+    pub fn is_sorted(&self) -> bool {
+        self.vec.is_sorted()
+    }
+
+    /// A sorted iterator over the vector.
+    pub fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
+        self.sort();
+        self.vec.presorted_iter()
+    }
+
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> PermutedIndexedIter<'_, T, Ix> {
+        self.sort();
+        self.vec.sorted_indexed_iter()
+    }
+
+    /// Get the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.vec.get(index)
+    }
+
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// Whether the vector is still sorted is checked when the reference is dropped.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_mut(&mut self, index: usize) -> Option<PresortedRefMut<'_, T, Natural, Ix>> {
+        let result = self.vec.get_mut(index);
+        if result.is_some() {
+            self.stats.sets += 1;
+        }
+        result
+    }
+}
+
+impl<T> From<Vec<T>> for AdaptiveSortVec<T> where T: Ord {
+    fn from(vec: Vec<T>) -> AdaptiveSortVec<T> {
+        AdaptiveSortVec {
+            vec: PresortedVec::from(vec),
+            stats: EditStats::default(),
+            last_strategy: None,
+        }
+    }
+}
+
+impl<T, Ix> Default for AdaptiveSortVec<T, Ix> where T: Ord, Ix: Index {
+    fn default() -> AdaptiveSortVec<T, Ix> {
+        AdaptiveSortVec::new_in()
+    }
+}
+
+#[cfg(feature = "heapsize")]
+impl<T, Ix> heapsize::HeapSizeOf for AdaptiveSortVec<T, Ix>
+    where T: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
+{
+    fn heap_size_of_children(&self) -> usize {
+        self.vec.heap_size_of_children()
+    }
+}
+
+#[test]
+fn test_strategy() {
+    let mut vec = AdaptiveSortVec::from((0..100).collect::<Vec<usize>>());
+    assert_eq!(vec.edit_stats().displaced, 100);
+    assert_eq!(vec.choose_strategy(), SortStrategy::Full);
+    vec.sort();
+    assert_eq!(vec.last_strategy(), Some(SortStrategy::Full));
+    assert_eq!(vec.edit_stats(), EditStats::default());
+
+    vec.set(10, 55);
+    vec.set(20, 5);
+    assert_eq!(vec.edit_stats(), EditStats { displaced: 2, pushes: 0, sets: 2, truncations: 0 });
+    assert!(vec.get_mut(100).is_none());
+    assert_eq!(vec.edit_stats().sets, 2);
+    assert_eq!(vec.choose_strategy(), SortStrategy::Repair);
+    assert_eq!(vec.sorted_iter().take(8).collect::<Vec<&usize>>(), vec![&0, &1, &2, &3, &4, &5, &5, &6]);
+    assert_eq!(vec.last_strategy(), Some(SortStrategy::Repair));
+
+    vec.truncate(50);
+    vec.extend_edits((0..50).map(|value| value * 2));
+    assert_eq!(vec.edit_stats(), EditStats { displaced: 50, pushes: 50, sets: 0, truncations: 1 });
+    assert_eq!(vec.choose_strategy(), SortStrategy::Merge);
+    assert_eq!(vec.sorted_iter().take(6).collect::<Vec<&usize>>(), vec![&0, &0, &1, &2, &2, &3]);
+    assert_eq!(vec.last_strategy(), Some(SortStrategy::Merge));
+
+    vec.apply_edits((0..100).map(|index| (index, 100 - index)));
+    assert_eq!(vec.choose_strategy(), SortStrategy::Full);
+    assert_eq!(vec.sorted_iter().take(3).collect::<Vec<&usize>>(), vec![&1, &2, &3]);
+    assert_eq!(vec.is_sorted(), true);
+}
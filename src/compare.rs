@@ -0,0 +1,33 @@
+use std::cmp::Ordering;
+
+/// The trait of comparators, used to order the elements of a presorted vector.
+pub trait Compare<T> {
+    /// Compare two values.
+    fn compare(&self, value_1: &T, value_2: &T) -> Ordering;
+}
+
+/// The natural order on a type, given by its `Ord` implementation.
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub struct Natural;
+
+impl<T> Compare<T> for Natural where T: Ord {
+    fn compare(&self, value_1: &T, value_2: &T) -> Ordering {
+        value_1.cmp(value_2)
+    }
+}
+
+impl<T, F> Compare<T> for F where F: Fn(&T, &T) -> Ordering {
+    fn compare(&self, value_1: &T, value_2: &T) -> Ordering {
+        self(value_1, value_2)
+    }
+}
+
+/// The order on a type given by comparing a key extracted from each value.
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub struct ByKey<F>(pub F);
+
+impl<T, K, F> Compare<T> for ByKey<F> where F: Fn(&T) -> K, K: Ord {
+    fn compare(&self, value_1: &T, value_2: &T) -> Ordering {
+        (self.0)(value_1).cmp(&(self.0)(value_2))
+    }
+}
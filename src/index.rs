@@ -0,0 +1,61 @@
+use std::fmt::Debug;
+
+/// The trait of types used to store indexes into a vector,
+/// for example in the permutation of a permuted vector.
+///
+/// Smaller index types make the permutation smaller,
+/// but limit the length of the vector: adding an element whose index
+/// does not fit in the index type panics. The default index type is `usize`.
+pub trait Index: Copy + Debug + Ord + 'static {
+    /// The largest index this type can hold.
+    const MAX: usize;
+
+    /// Convert an index into this type.
+    /// Panics if the index does not fit.
+    fn from_usize(index: usize) -> Self;
+
+    /// Convert this type into an index.
+    fn to_usize(self) -> usize;
+}
+
+impl Index for usize {
+    const MAX: usize = usize::MAX;
+    fn from_usize(index: usize) -> usize {
+        index
+    }
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+macro_rules! impl_index {
+    ($type: ty) => {
+        impl Index for $type {
+            const MAX: usize = <$type>::MAX as usize;
+            fn from_usize(index: usize) -> $type {
+                assert!(index <= <$type>::MAX as usize, "index {} does not fit in {}", index, stringify!($type));
+                index as $type
+            }
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        }
+    }
+}
+
+impl_index!(u16);
+impl_index!(u32);
+
+#[test]
+fn test_index() {
+    assert_eq!(u16::from_usize(65535).to_usize(), 65535);
+    assert_eq!(u32::from_usize(70000).to_usize(), 70000);
+    assert_eq!(usize::from_usize(70000).to_usize(), 70000);
+    assert_eq!(u16::MAX, 65535);
+}
+
+#[test]
+#[should_panic]
+fn test_index_overflow() {
+    u16::from_usize(65536);
+}
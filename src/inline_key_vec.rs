@@ -0,0 +1,419 @@
+use std::iter::FusedIterator;
+use std::mem;
+use std::slice::Iter;
+
+use index::Index;
+#[cfg(feature = "stats")]
+use stats::Stats;
+use stats::Counters;
+
+/// The type of permuted vectors sorted by a key stored inline in the permutation.
+///
+/// The permutation is an array of `(key, index)` pairs, so sorting compares keys
+/// without looking up the contents, and both sorting and sorted iteration
+/// stream through one contiguous array. This suits small `Copy` keys,
+/// especially of large elements, and index types such as `u32` keep the pairs small.
+///
+/// Elements which are pushed or set out of order with their neighbours,
+/// or edited in a batch, are remembered as displaced. Sorting takes the displaced
+/// entries out of the permutation, sorts them, and merges them back,
+/// without comparing the entries which are still in order.
+/// If most of the elements are displaced, the whole permutation is sorted instead.
+#[derive(Clone,Debug)]
+pub struct InlineKeyVec<T, K, F = fn(&T) -> K, Ix = usize> {
+    // The contents of the vector.
+    contents: Vec<T>,
+    // The permutation, together with the key of each element
+    permutation: Vec<(K, Ix)>,
+    // The previous permutation, kept to reuse its allocation when sorting
+    spare_permutation: Vec<(K, Ix)>,
+    // The inverse permutation
+    inverse: Vec<Ix>,
+    // Which elements are displaced, and may be out of order
+    displaced: Vec<bool>,
+    // The displaced elements
+    displaced_indexes: Vec<Ix>,
+    // The function used to compute keys
+    key: F,
+    // Counters of the work done
+    counters: Counters,
+}
+
+/// The type of presorted iterators over a vector sorted by an inline key.
+#[derive(Clone,Debug)]
+pub struct InlineKeyIter<'a, T, K, Ix = usize> where T: 'a, K: 'a, Ix: 'a {
+    // The remaining permutation
+    permutation: Iter<'a, (K, Ix)>,
+    // The contents of the iterator
+    contents: &'a[T],
+}
+
+impl<'a, T, K, Ix> Iterator for InlineKeyIter<'a, T, K, Ix> where T: 'a, K: 'a, Ix: Index {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.next().map(|&(_, index)| &contents[index.to_usize()])
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.permutation.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth(n).map(|&(_, index)| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, K, Ix> DoubleEndedIterator for InlineKeyIter<'a, T, K, Ix> where T: 'a, K: 'a, Ix: Index {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.next_back().map(|&(_, index)| &contents[index.to_usize()])
+    }
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth_back(n).map(|&(_, index)| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, K, Ix> ExactSizeIterator for InlineKeyIter<'a, T, K, Ix> where T: 'a, K: 'a, Ix: Index {}
+
+impl<'a, T, K, Ix> FusedIterator for InlineKeyIter<'a, T, K, Ix> where T: 'a, K: 'a, Ix: Index {}
+
+impl<T, K, F> InlineKeyVec<T, K, F> where K: Ord + Copy, F: Fn(&T) -> K {
+    /// Create a new, empty vector, sorted by the key computed by `key`.
+    pub fn new(key: F) -> InlineKeyVec<T, K, F> {
+        InlineKeyVec::new_in(key)
+    }
+}
+
+impl<T, K, F, Ix> InlineKeyVec<T, K, F, Ix> where K: Ord + Copy, F: Fn(&T) -> K, Ix: Index {
+    /// Create a new, empty vector, sorted by the key computed by `key`,
+    /// for any index type, for example `InlineKeyVec::<T, K, F, u32>::new_in(key)`.
+    pub fn new_in(key: F) -> InlineKeyVec<T, K, F, Ix> {
+        InlineKeyVec {
+            contents: Vec::new(),
+            permutation: Vec::new(),
+            spare_permutation: Vec::new(),
+            inverse: Vec::new(),
+            displaced: Vec::new(),
+            displaced_indexes: Vec::new(),
+            key,
+            counters: Counters::default(),
+        }
+    }
+
+    /// The length of the vector.
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    /// Append an element to the end of the vector.
+    pub fn push(&mut self, value: T) {
+        let index = self.contents.len();
+        self.permutation.push(((self.key)(&value), Ix::from_usize(index)));
+        self.inverse.push(Ix::from_usize(index));
+        self.displaced.push(false);
+        self.contents.push(value);
+        if !self.is_sorted_at(index) {
+            self.mark_displaced(index);
+        }
+    }
+
+    /// Set the `i`th element of the vector.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn set(&mut self, index: usize, value: T) {
+        let permuted = self.inverse[index].to_usize();
+        self.permutation[permuted].0 = (self.key)(&value);
+        self.contents[index] = value;
+        if !self.displaced[index] && !self.is_sorted_at(permuted) {
+            self.mark_displaced(index);
+        }
+    }
+
+    /// Set many elements of the vector at once.
+    /// The key of each edited element is computed once per edit,
+    /// but the elements are not compared with their neighbours until the next sort.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        for (index, value) in edits {
+            let permuted = self.inverse[index].to_usize();
+            self.permutation[permuted].0 = (self.key)(&value);
+            self.contents[index] = value;
+            self.mark_displaced(index);
+        }
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// The appended elements are not compared with their neighbours until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        for value in values {
+            let index = self.contents.len();
+            self.permutation.push(((self.key)(&value), Ix::from_usize(index)));
+            self.inverse.push(Ix::from_usize(index));
+            self.displaced.push(false);
+            self.contents.push(value);
+            self.mark_displaced(index);
+        }
+    }
+
+    // Is the entry at a position in the permutation in order with its neighbours?
+    // Displaced neighbours may yet move, so an entry next to one is never in order.
+    fn is_sorted_at(&self, permuted: usize) -> bool {
+        let entry = &self.permutation[permuted];
+        let before = permuted.checked_sub(1).map(|before| &self.permutation[before]);
+        let after = self.permutation.get(permuted + 1);
+        before.iter().chain(after.iter()).all(|&&(_, index)| !self.displaced[index.to_usize()]) && {
+            self.counters.compared(before.iter().chain(after.iter()).count());
+            before.map(|before| before <= entry).unwrap_or(true) &&
+            after.map(|after| entry <= after).unwrap_or(true)
+        }
+    }
+
+    // Remember that the `i`th element is displaced, so may be out of order.
+    fn mark_displaced(&mut self, index: usize) {
+        if !self.displaced[index] {
+            self.displaced[index] = true;
+            self.displaced_indexes.push(Ix::from_usize(index));
+        }
+    }
+
+    // Forget that any element is displaced,
+    // which is only safe if the permutation is sorted.
+    fn mark_sorted(&mut self) {
+        for &index in &self.displaced_indexes {
+            self.displaced[index.to_usize()] = false;
+        }
+        self.displaced_indexes.clear();
+    }
+
+    /// Truncate this vector.
+    /// This does not change whether the vector is sorted.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.contents.truncate(len);
+            self.permutation.retain(|&(_, index)| index.to_usize() < len);
+            self.displaced.truncate(len);
+            self.displaced_indexes.retain(|&index| index.to_usize() < len);
+            self.reset_inverse();
+        }
+    }
+
+    /// Sort the permutation on the vector.
+    /// Ties between equal keys are broken by index.
+    pub fn sort(&mut self) {
+        if self.is_sorted() {
+            self.counters.skipped_sort();
+        } else if 2 * self.displaced_indexes.len() > self.len() {
+            // Most of the elements are displaced
+            let mut comparisons = 0;
+            self.permutation.sort_by(|entry_1, entry_2| { comparisons += 1; entry_1.cmp(entry_2) });
+            self.counters.compared(comparisons);
+            self.counters.full_sort();
+            self.reset_inverse();
+        } else {
+            self.repair();
+        }
+        self.mark_sorted();
+    }
+
+    // Sort the permutation by taking out the displaced entries, sorting them,
+    // and merging them back into the entries which are still in order.
+    fn repair(&mut self) {
+        let mut comparisons = 0;
+        let displaced = &self.displaced;
+        let mut entries: Vec<(K, Ix)> = self.displaced_indexes.iter()
+            .map(|&index| self.permutation[self.inverse[index.to_usize()].to_usize()])
+            .collect();
+        entries.sort_by(|entry_1, entry_2| { comparisons += 1; entry_1.cmp(entry_2) });
+        self.permutation.retain(|&(_, index)| !displaced[index.to_usize()]);
+        let mut new_permutation = mem::take(&mut self.spare_permutation);
+        new_permutation.clear();
+        new_permutation.reserve(self.contents.len());
+        let in_order = mem::replace(&mut self.permutation, new_permutation);
+        let mut start = 0;
+        for entry in entries {
+            // Binary search the entries after the previous insertion
+            let end = start + in_order[start..].partition_point(|other| { comparisons += 1; *other < entry });
+            self.permutation.extend_from_slice(&in_order[start..end]);
+            self.permutation.push(entry);
+            start = end;
+        }
+        self.permutation.extend_from_slice(&in_order[start..]);
+        self.spare_permutation = in_order;
+        self.counters.compared(comparisons);
+        self.reset_inverse();
+    }
+
+    // Recompute the inverse permutation from the permutation.
+    fn reset_inverse(&mut self) {
+        self.inverse.resize(self.contents.len(), Ix::from_usize(0));
+        for (permuted, &(_, index)) in self.permutation.iter().enumerate() {
+            self.inverse[index.to_usize()] = Ix::from_usize(permuted);
+        }
+    }
+
+    /// An iterator over the presorted vector
+    pub fn presorted_iter(&self) -> InlineKeyIter<'_, T, K, Ix> {
+        InlineKeyIter {
+            permutation: self.permutation.iter(),
+            contents: &self.contents,
+        }
+    }
+
+    /// Is the presorted vector already sorted?
+    /// Only the displaced entries are compared with their neighbours.
+    pub fn is_sorted(&self) -> bool {
+        let mut comparisons = 0;
+        let result = self.displaced_indexes.iter().all(|&index| {
+            let permuted = self.inverse[index.to_usize()].to_usize();
+            let entry = &self.permutation[permuted];
+            // The entry before a displaced entry is compared with it, unless it is also displaced
+            let before = permuted.checked_sub(1).map(|before| &self.permutation[before]);
+            let after = self.permutation.get(permuted + 1);
+            before.map(|before| self.displaced[before.1.to_usize()] || { comparisons += 1; before <= entry }).unwrap_or(true) &&
+            after.map(|after| { comparisons += 1; entry <= after }).unwrap_or(true)
+        });
+        self.counters.compared(comparisons);
+        result
+    }
+
+    /// The number of displaced elements, which may be out of order.
+    pub fn displaced_count(&self) -> usize {
+        self.displaced_indexes.len()
+    }
+
+    /// A sorted iterator over the vector.
+    pub fn sorted_iter(&mut self) -> InlineKeyIter<'_, T, K, Ix> {
+        self.sort();
+        self.presorted_iter()
+    }
+
+    /// Get the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.contents.get(index)
+    }
+
+    /// Get the key of the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_key(&self, index: usize) -> Option<K> {
+        self.inverse.get(index).map(|&permuted| self.permutation[permuted.to_usize()].0)
+    }
+
+    /// The rank of the `i`th element in sorted order.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn rank_of(&mut self, index: usize) -> usize {
+        self.sort();
+        self.inverse[index].to_usize()
+    }
+
+    /// The index of the `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn index_of_nth_sorted(&mut self, k: usize) -> Option<usize> {
+        self.sort();
+        self.permutation.get(k).map(|&(_, index)| index.to_usize())
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    /// Comparisons are made between inline keys.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.get()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.counters.reset();
+    }
+}
+
+#[cfg(feature = "heapsize")]
+impl<T, K, F, Ix> heapsize::HeapSizeOf for InlineKeyVec<T, K, F, Ix>
+    where T: heapsize::HeapSizeOf, K: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
+{
+    fn heap_size_of_children(&self) -> usize {
+        self.contents.heap_size_of_children() + self.permutation.heap_size_of_children() +
+        self.spare_permutation.heap_size_of_children() + self.inverse.heap_size_of_children() +
+        self.displaced.heap_size_of_children() + self.displaced_indexes.heap_size_of_children()
+    }
+}
+
+#[test]
+fn test_push_set() {
+    let mut vec = InlineKeyVec::new(|value: &(u8, [u64; 4])| value.0);
+    vec.push((30, [0; 4]));
+    vec.push((40, [1; 4]));
+    assert_eq!(vec.is_sorted(), true);
+    vec.push((20, [2; 4]));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().map(|value| value.1[0]).collect::<Vec<u64>>(), vec![2, 0, 1]);
+    assert_eq!(vec.rank_of(0), 1);
+    assert_eq!(vec.index_of_nth_sorted(2), Some(1));
+
+    vec.set(0, (35, [3; 4]));
+    assert_eq!(vec.get_key(0), Some(35));
+    assert_eq!(vec.is_sorted(), true);
+    vec.set(1, (10, [4; 4]));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().map(|value| value.0).collect::<Vec<u8>>(), vec![10, 20, 35]);
+
+    vec.apply_edits(vec![(2, (50, [5; 4])), (1, (10, [6; 4]))]);
+    vec.extend_edits(vec![(60, [7; 4]), (10, [8; 4])]);
+    assert_eq!(vec.len(), 5);
+    assert_eq!(vec.sorted_iter().map(|value| value.1[0]).collect::<Vec<u64>>(), vec![6, 8, 3, 5, 7]);
+    assert_eq!(vec.sorted_iter().rev().nth(1).map(|value| value.0), Some(50));
+
+    vec.truncate(2);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().map(|value| value.0).collect::<Vec<u8>>(), vec![10, 35]);
+    assert_eq!(vec.get_key(2), None);
+}
+
+#[test]
+fn test_displaced() {
+    let mut vec = InlineKeyVec::new(|value: &usize| *value);
+    for value in 0..100 {
+        vec.push(value * 10);
+    }
+    assert_eq!(vec.displaced_count(), 0);
+    vec.set(10, 105);
+    assert_eq!(vec.displaced_count(), 0);
+    vec.set(20, 5);
+    vec.set(21, 5);
+    vec.push(1);
+    assert_eq!(vec.displaced_count(), 3);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().take(5).collect::<Vec<&usize>>(), vec![&0, &1, &5, &5, &10]);
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.rank_of(10), 13);
+
+    vec.apply_edits(vec![(30, 305), (40, 405)]);
+    assert_eq!(vec.displaced_count(), 2);
+    assert_eq!(vec.is_sorted(), true);
+    vec.sort();
+    assert_eq!(vec.displaced_count(), 0);
+}
+
+#[test]
+fn test_keys_not_recomputed() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut vec: InlineKeyVec<usize, usize, _, u16> = InlineKeyVec::new_in(|value: &usize| { calls.set(calls.get() + 1); *value });
+    vec.extend_edits(vec![3, 1, 2, 1]);
+    vec.push(0);
+    vec.set(0, 4);
+    assert_eq!(calls.get(), 6);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &1, &1, &2, &4]);
+    assert_eq!(vec.index_of_nth_sorted(1), Some(1));
+    assert_eq!(vec.rank_of(0), 4);
+    vec.set(4, 5);
+    vec.sort();
+    assert_eq!(vec.get_key(4), Some(5));
+    assert_eq!(calls.get(), 7);
+}
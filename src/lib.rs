@@ -5,6 +5,18 @@
 //! This allows the vector to be updated, and
 //! if the updates preserve sort order, then the next `vec.sort()`
 //! will be O(n) rather than O(n log n).
+//!
+//! All of the vector types implement the `SortVec` trait, so code can be
+//! written generically over the choice of sorting strategy.
+//!
+//! With the `stats` feature, each vector counts the comparisons and sorts it does,
+//! which is useful for checking that the fast paths are being taken.
+//!
+//! The permuted, presorted and merge vectors are generic over the `Index` type
+//! used to store their permutations, so for example `PermutedVec<T, u32>`
+//! saves space for vectors of fewer than 2^32 elements.
+
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 
 #[cfg(feature = "serde")]
 extern crate serde;
@@ -12,10 +24,24 @@ extern crate serde;
 #[cfg(feature = "heapsize")]
 extern crate heapsize;
 
+pub mod compare;
+pub mod index;
 pub mod permuted_vec;
 pub mod presorted_vec;
+pub mod presorted_vec_by_key;
+pub mod inline_key_vec;
 pub mod merge_vec;
+pub mod adaptive_vec;
+pub mod radix_vec;
+pub mod sort_vec;
+pub mod stats;
 
+pub use index::Index;
 pub use permuted_vec::PermutedVec;
 pub use presorted_vec::PresortedVec;
+pub use presorted_vec_by_key::PresortedVecByKey;
+pub use inline_key_vec::InlineKeyVec;
 pub use merge_vec::MergeVec;
+pub use adaptive_vec::AdaptiveSortVec;
+pub use radix_vec::RadixPresortedVec;
+pub use sort_vec::SortVec;
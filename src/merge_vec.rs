@@ -1,31 +1,67 @@
 use std::cmp::Ordering;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::{Deref, DerefMut, RangeBounds};
+use std::slice::Iter;
+
+use index::Index;
+use permuted_vec::{count_inversions, count_runs, range_positions};
+#[cfg(feature = "stats")]
+use stats::Stats;
+use stats::Counters;
 
 
 /// The type of merge vectors.
+///
+/// By default, equal elements are sorted in no particular order.
+/// A vector created by `new_stable` sorts equal elements by their index,
+/// as a full sort of a `PermutedVec` does.
+///
+/// When an element is set, it is compared with the nearest sorted elements on either side,
+/// skipping over at most `search_distance()` unsorted or removed entries in the sort order.
+///
+/// Indexes into the content and positions in the sort order are stored using the index type `Ix`.
+/// Removed entries are reclaimed before the sort order outgrows the index type,
+/// so the index type only needs to hold the length of the vector.
 #[derive(Clone,Debug)]
-pub struct MergeVec<T> where T: Ord {
+pub struct MergeVec<T, Ix = usize> where T: Ord {
     content: Vec<T>,                // the content changed only by user
-    sort_index: Vec<usize>,         // where the sort reference is stored
-    sorted: Vec<SortTarget>,        //  holes if changed
-    unsorted: Vec<Option<usize>>    // new, unsorted content
+    sort_index: Vec<Ix>,            // where the sort reference is stored
+    sorted: Vec<SortTarget<Ix>>,    //  holes if changed
+    spare_sorted: Vec<SortTarget<Ix>>, // the previous sort order, kept to reuse its allocation
+    unsorted: Vec<Option<Ix>>,      // new, unsorted content
+    edited: Vec<Ix>,                // content made unsorted by a batch edit, checked at sort
+    stable: bool,                   // whether ties are broken by index
+    search_distance: usize,         // how many unsorted or removed entries to skip looking for neighbours
+    counters: Counters,             // counters of the work done
 }
 
+// The default number of unsorted or removed entries skipped looking for the neighbours of an element
+const DEFAULT_SEARCH_DISTANCE: usize = 16;
+
 #[derive(Clone,Copy,Debug)]
-enum SortTarget {
-    Content(usize),
-    Unsorted(usize),
+enum SortTarget<Ix> {
+    Content(Ix),
+    Unsorted(Ix),
     Removed,
 }
 
+#[cfg(feature = "heapsize")]
+impl<Ix> heapsize::HeapSizeOf for SortTarget<Ix> {
+    fn heap_size_of_children(&self) -> usize {
+        0
+    }
+}
+
 #[derive(Debug)]
-struct ContentIter<'a> {
+struct ContentIter<'a, Ix> where Ix: 'a {
     index: usize,
-    vec: &'a Vec<SortTarget>,
+    vec: &'a Vec<SortTarget<Ix>>,
 }
 
-impl<'a> Iterator for ContentIter<'a> {
-    type Item = usize;
-    fn next(&mut self) -> Option<usize> {
+impl<'a, Ix> Iterator for ContentIter<'a, Ix> where Ix: 'a + Index {
+    type Item = Ix;
+    fn next(&mut self) -> Option<Ix> {
         while self.index < self.vec.len() {
             let i = self.index;
             self.index += 1;
@@ -33,95 +69,321 @@ impl<'a> Iterator for ContentIter<'a> {
                 return Some(c);
             }
         }
-        return None
+        None
     }
 }
 
-#[derive(Debug)]
-struct OpContentIter<'a> {
+/// A mutable reference to an element of a merge vector,
+/// which checks whether the element is back in order when it is dropped.
+pub struct MergeVecRefMut<'a, T, Ix = usize> where T: 'a + Ord, Ix: 'a + Index {
+    vec: &'a mut MergeVec<T, Ix>,
     index: usize,
-    vec: &'a Vec<Option<usize>>,
 }
 
-impl<'a> Iterator for OpContentIter<'a> {
-    type Item = usize;
-    fn next(&mut self) -> Option<usize> {
-        while self.index < self.vec.len() {
-            let i = self.index;
-            self.index += 1;
-            if let Some(c) = self.vec[i] {
-                return Some(c);
-            }
-        }
-        return None
+impl<'a, T, Ix> Deref for MergeVecRefMut<'a, T, Ix> where T: 'a + Ord, Ix: 'a + Index {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.vec.content[self.index]
+    }
+}
+
+impl<'a, T, Ix> DerefMut for MergeVecRefMut<'a, T, Ix> where T: 'a + Ord, Ix: 'a + Index {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.vec.content[self.index]
     }
 }
 
+impl<'a, T, Ix> Drop for MergeVecRefMut<'a, T, Ix> where T: 'a + Ord, Ix: 'a + Index {
+    fn drop(&mut self) {
+        self.vec.update_sort_target(self.index);
+    }
+}
+
+/// The type of sorted iterators over a merge vector.
 #[derive(Clone,Debug)]
-pub struct MergeVecIter<'a, T> where T: 'a {
-    index: usize,
+pub struct MergeVecIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
     content: &'a[T],
-    sort_order: &'a[SortTarget],
+    // the remaining sort order, which contains only content after a sort
+    sort_order: Iter<'a, SortTarget<Ix>>,
 }
 
-impl<'a, T> Iterator for MergeVecIter<'a, T> where T: 'a {
+// the content a sort target refers to, in a sorted vector
+fn sorted_content<T, Ix: Index>(content: &[T], target: SortTarget<Ix>) -> &T {
+    match target {
+        SortTarget::Content(i) => &content[i.to_usize()],
+        _ => unreachable!("sorted merge vectors only contain content"),
+    }
+}
+
+impl<'a, T, Ix> Iterator for MergeVecIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {
     type Item = &'a T;
     fn next(&mut self) -> Option<&'a T> {
-        let sorted_index = self.index;
-        self.index += 1;
-        self.sort_order.get(sorted_index).and_then(|&target| {
-            match target {
-                SortTarget::Content(i) => self.content.get(i),
-                _ => None
-            }  
-        })
+        let content = self.content;
+        self.sort_order.next().map(|&target| sorted_content(content, target))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.sort_order.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        let content = self.content;
+        self.sort_order.nth(n).map(|&target| sorted_content(content, target))
     }
 }
 
-// borrow helper to separate the two fields into different mutability classes
-fn sort_new_indexes<T: Ord>(indexes: &mut Vec<Option<usize>>, content: &Vec<T>) {
+impl<'a, T, Ix> DoubleEndedIterator for MergeVecIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let content = self.content;
+        self.sort_order.next_back().map(|&target| sorted_content(content, target))
+    }
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        let content = self.content;
+        self.sort_order.nth_back(n).map(|&target| sorted_content(content, target))
+    }
+}
+
+impl<'a, T, Ix> ExactSizeIterator for MergeVecIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {}
+
+impl<'a, T, Ix> FusedIterator for MergeVecIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {}
+
+/// The type of sorted iterators over a merge vector,
+/// which yield each element together with its index in the vector.
+#[derive(Clone,Debug)]
+pub struct MergeVecIndexedIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
+    content: &'a[T],
+    // the remaining sort order, which contains only content after a sort
+    sort_order: Iter<'a, SortTarget<Ix>>,
+}
+
+// the index and content a sort target refers to, in a sorted vector
+fn sorted_indexed_content<T, Ix: Index>(content: &[T], target: SortTarget<Ix>) -> (usize, &T) {
+    match target {
+        SortTarget::Content(i) => (i.to_usize(), &content[i.to_usize()]),
+        _ => unreachable!("sorted merge vectors only contain content"),
+    }
+}
+
+impl<'a, T, Ix> Iterator for MergeVecIndexedIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {
+    type Item = (usize, &'a T);
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        let content = self.content;
+        self.sort_order.next().map(|&target| sorted_indexed_content(content, target))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.sort_order.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<(usize, &'a T)> {
+        let content = self.content;
+        self.sort_order.nth(n).map(|&target| sorted_indexed_content(content, target))
+    }
+}
+
+impl<'a, T, Ix> DoubleEndedIterator for MergeVecIndexedIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {
+    fn next_back(&mut self) -> Option<(usize, &'a T)> {
+        let content = self.content;
+        self.sort_order.next_back().map(|&target| sorted_indexed_content(content, target))
+    }
+    fn nth_back(&mut self, n: usize) -> Option<(usize, &'a T)> {
+        let content = self.content;
+        self.sort_order.nth_back(n).map(|&target| sorted_indexed_content(content, target))
+    }
+}
+
+impl<'a, T, Ix> ExactSizeIterator for MergeVecIndexedIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {}
+
+impl<'a, T, Ix> FusedIterator for MergeVecIndexedIter<'a, T, Ix> where T: 'a, Ix: 'a + Index {}
+
+// compare content values at the given indexes, breaking ties by index if the vector is stable
+fn compare_content<T: Ord>(stable: bool, value_a: &T, index_a: usize, value_b: &T, index_b: usize) -> Ordering {
+    match value_a.cmp(value_b) {
+        Ordering::Equal if stable => index_a.cmp(&index_b),
+        ord => ord,
+    }
+}
+
+// borrow helper to separate the two fields into different mutability classes,
+// returning the number of comparisons between content
+fn sort_new_indexes<T: Ord, Ix: Index>(indexes: &mut [Option<Ix>], content: &[T], stable: bool) -> usize {
+    let mut comparisons = 0;
     indexes.sort_by(|&a,&b| {
         match (a, b) {
             (None, None) => Ordering::Equal,
             (None, _) => Ordering::Less,
             (_, None) => Ordering::Greater,
-            (Some(a), Some(b)) => { content[a].cmp(&content[b]) }
+            (Some(a), Some(b)) => {
+                let (a, b) = (a.to_usize(), b.to_usize());
+                comparisons += 1;
+                compare_content(stable, &content[a], a, &content[b], b)
+            }
         }
     });
+    comparisons
+}
+
+// the number of entries at the start of the sorted content which are no greater than the given content,
+// found by galloping back from the end, so merging k elements into n takes O(k log n) comparisons
+fn gallop_back<T: Ord, Ix: Index>(sorted: &[SortTarget<Ix>], content: &[T], stable: bool, index: usize, comparisons: &mut usize) -> usize {
+    let mut greater = |target: &SortTarget<Ix>| match *target {
+        SortTarget::Content(sorted_index) => {
+            let sorted_index = sorted_index.to_usize();
+            *comparisons += 1;
+            compare_content(stable, &content[sorted_index], sorted_index, &content[index], index) == Ordering::Greater
+        }
+        _ => unreachable!("only content is merged"),
+    };
+    // the entries from `upper` on are known to be greater
+    let mut upper = sorted.len();
+    let mut step = 1;
+    while upper > 0 {
+        let probe = upper.saturating_sub(step);
+        if !greater(&sorted[probe]) {
+            return probe + 1 + sorted[probe + 1..upper].partition_point(|target| !greater(target));
+        }
+        upper = probe;
+        step *= 2;
+    }
+    0
+}
+
+// a longest non-decreasing subsequence of the content, found by patience sorting,
+// as a mask of the indexes in the subsequence, together with the number of comparisons made
+fn longest_sorted_subsequence<T: Ord>(content: &[T]) -> (Vec<bool>, usize) {
+    let mut comparisons = 0;
+    // the index of the smallest last element of a subsequence of each length
+    let mut tails: Vec<usize> = Vec::new();
+    // the index of the previous element in the subsequence ending at each element
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(content.len());
+    for index in 0..content.len() {
+        let length = match tails.last() {
+            // extending the longest subsequence is common, so check it first
+            Some(&last) if { comparisons += 1; content[last] <= content[index] } => tails.len(),
+            _ => tails.partition_point(|&tail| { comparisons += 1; content[tail] <= content[index] }),
+        };
+        previous.push(if length > 0 { Some(tails[length - 1]) } else { None });
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+    let mut in_subsequence = vec![false; content.len()];
+    let mut next = tails.last().cloned();
+    while let Some(index) = next {
+        in_subsequence[index] = true;
+        next = previous[index];
+    }
+    (in_subsequence, comparisons)
 }
 
 impl<T> MergeVec<T> where T: Ord {
+    /// Create a new, empty vector.
+    pub fn new() -> MergeVec<T> {
+        MergeVec::new_in()
+    }
+
+    /// Create a new, empty vector, with space for `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> MergeVec<T> {
+        MergeVec::with_capacity_in(capacity)
+    }
+
+    /// Create a new, empty vector, in which equal elements are sorted by their index.
+    pub fn new_stable() -> MergeVec<T> {
+        MergeVec::new_stable_in()
+    }
+
+    /// Create a vector from the given elements, in which equal elements are sorted by their index.
+    /// As with `MergeVec::from`, a longest sorted subsequence is kept in place.
+    pub fn from_vec_stable(vec: Vec<T>) -> MergeVec<T> {
+        MergeVec::from_vec_stable_in(vec)
+    }
+}
+
+impl<T, Ix> MergeVec<T, Ix> where T: Ord, Ix: Index {
+    /// Create a new, empty vector,
+    /// for any index type, for example `MergeVec::<T, u32>::new_in()`.
+    pub fn new_in() -> MergeVec<T, Ix> {
+        MergeVec::default()
+    }
+
+    /// Create a new, empty vector, with space for `capacity` elements,
+    /// for any index type.
+    pub fn with_capacity_in(capacity: usize) -> MergeVec<T, Ix> {
+        let mut result = MergeVec::new_in();
+        result.reserve(capacity);
+        result
+    }
+
+    /// Create a new, empty vector, in which equal elements are sorted by their index,
+    /// for any index type.
+    pub fn new_stable_in() -> MergeVec<T, Ix> {
+        MergeVec {
+            stable: true,
+            .. MergeVec::new_in()
+        }
+    }
+
+    /// Create a vector from the given elements, in which equal elements are sorted by their index,
+    /// for any index type.
+    pub fn from_vec_stable_in(vec: Vec<T>) -> MergeVec<T, Ix> {
+        MergeVec::from_content(vec, true)
+    }
+
+    // a longest sorted subsequence is kept in place, and only the rest of the vector is unsorted
+    fn from_content(content: Vec<T>, stable: bool) -> MergeVec<T, Ix> {
+        let length = content.len();
+        let (in_order, comparisons) = longest_sorted_subsequence(&content);
+        let mut sorted = Vec::with_capacity(length);
+        let mut unsorted = Vec::new();
+        for (index, in_order) in in_order.into_iter().enumerate() {
+            if in_order {
+                sorted.push(SortTarget::Content(Ix::from_usize(index)));
+            } else {
+                sorted.push(SortTarget::Unsorted(Ix::from_usize(unsorted.len())));
+                unsorted.push(Some(Ix::from_usize(index)));
+            }
+        }
+        let counters = Counters::default();
+        counters.compared(comparisons);
+        counters.unsorted(unsorted.len());
+        MergeVec {
+            content,
+            sort_index: (0..length).map(Ix::from_usize).collect(),
+            sorted,
+            spare_sorted: Vec::new(),
+            unsorted,
+            edited: Vec::new(),
+            stable,
+            search_distance: DEFAULT_SEARCH_DISTANCE,
+            counters,
+        }
+    }
+
     /// The length of the vector.
     pub fn len(&self) -> usize {
         self.content.len()
     }
 
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
     /// Append an element to the end of the content.
     pub fn push(&mut self, value: T) {
         //println!("vec push index {:?}", self.len());
+        let content_index = self.push_removed(value);
+        // in order if it is no less than the last content in the sort order
+        self.update_sort_target(content_index);
+    }
 
-        if self.content.len() == 0 {
-            // empty
-            self.sort_index.push(0);
-            self.sorted.push(SortTarget::Content(0));
-            self.content.push(value);
-            return;
-        }
+    // append an element to the content, with a removed entry at the end of the sort order
+    fn push_removed(&mut self, value: T) -> usize {
+        self.reclaim_if_full();
         let content_index = self.content.len();
-        if value >= self.content[content_index - 1] {
-            // in order
-            let sort_index = self.sorted.len();
-            self.sort_index.push(sort_index);
-            self.sorted.push(SortTarget::Content(content_index));
-        } else {
-            // out of order
-            let new_index = self.unsorted.len();
-            let sort_index = self.sorted.len();
-            self.sort_index.push(sort_index);  
-            self.sorted.push(SortTarget::Unsorted(new_index));
-            self.unsorted.push(Some(content_index));
-        }
+        let sort_index = self.sorted.len();
         self.content.push(value);
+        self.sort_index.push(Ix::from_usize(sort_index));
+        self.sorted.push(SortTarget::Removed);
+        content_index
     }
 
     
@@ -129,53 +391,130 @@ impl<T> MergeVec<T> where T: Ord {
     /// Panics if the vector contains fewer than `i` elements.
     pub fn set(&mut self, index: usize, value: T) {
         //println!("vec set index {:?}", index);
-        let sort_index = self.sort_index[index];
-        let old_loc = self.sorted[sort_index];
-        match old_loc {
+        self.content[index] = value;
+        self.reclaim_if_full();
+        self.update_sort_target(index);
+    }
+
+    // move the `i`th element in or out of the sorted content,
+    // depending on whether it is in order with its neighbours
+    fn update_sort_target(&mut self, index: usize) {
+        let sort_index = self.sort_index[index].to_usize();
+        let would_be_sorted = self.would_be_sorted(sort_index, index, &self.content[index]);
+        match self.sorted[sort_index] {
             SortTarget::Content(_) => {
-                if !self.would_be_sorted(sort_index, &value) {
+                if !would_be_sorted {
                     let new_index = self.unsorted.len();
-                    self.sorted[sort_index] = SortTarget::Unsorted(new_index);
-                    self.unsorted.push(Some(index));
+                    self.sorted[sort_index] = SortTarget::Unsorted(Ix::from_usize(new_index));
+                    self.unsorted.push(Some(Ix::from_usize(index)));
+                    self.counters.unsorted(1);
                 }
             }
             SortTarget::Unsorted(unsort_index) => {
-                if self.would_be_sorted(sort_index, &value) {
-                    self.unsorted[unsort_index] = None;
-                    self.sorted[sort_index] = SortTarget::Content(index);
+                if would_be_sorted {
+                    self.unsorted[unsort_index.to_usize()] = None;
+                    self.sorted[sort_index] = SortTarget::Content(Ix::from_usize(index));
                 }
             }
             SortTarget::Removed => {
-                if self.would_be_sorted(sort_index, &value) {
-                    self.sorted[sort_index] = SortTarget::Content(index);
+                if would_be_sorted {
+                    self.sorted[sort_index] = SortTarget::Content(Ix::from_usize(index));
                 } else {
                     let new_index = self.unsorted.len();
-                    self.sorted[sort_index] = SortTarget::Unsorted(new_index);
-                    self.unsorted.push(Some(index));
+                    self.sorted[sort_index] = SortTarget::Unsorted(Ix::from_usize(new_index));
+                    self.unsorted.push(Some(Ix::from_usize(index)));
+                    self.counters.unsorted(1);
                 }
             }
         }
-        self.content[index] = value;
     }
 
-    fn would_be_sorted(&self, sort_index: usize, value: &T) -> bool {
-        if sort_index > 0 {
-            if let SortTarget::Content(content_index) = self.sorted[sort_index - 1] {
-                if self.content[content_index] > *value { return false; }
-            } else {
-                // TODO: Should we search through unsorted/removed values?
-                return false;
+    /// Set many elements of the vector at once.
+    /// Unlike `set`, the edited elements are moved out of the sorted content
+    /// without comparing them with their neighbours, and are checked once at the next sort,
+    /// when those still in order are moved back.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        for (index, value) in edits {
+            self.content[index] = value;
+            self.reclaim_if_full();
+            self.unsort(index);
+        }
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// Unlike `push`, the appended elements are not compared with their neighbours
+    /// until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        for value in values {
+            let index = self.push_removed(value);
+            self.unsort(index);
+        }
+    }
+
+    // move the `i`th element out of the sorted content, to be checked at the next sort
+    fn unsort(&mut self, index: usize) {
+        let sort_index = self.sort_index[index].to_usize();
+        match self.sorted[sort_index] {
+            SortTarget::Unsorted(_) => {}
+            _ => {
+                let new_index = self.unsorted.len();
+                self.sorted[sort_index] = SortTarget::Unsorted(Ix::from_usize(new_index));
+                self.unsorted.push(Some(Ix::from_usize(index)));
+                self.edited.push(Ix::from_usize(index));
+                self.counters.unsorted(1);
             }
         }
-        if sort_index < self.sorted.len() - 1 {
-            if let SortTarget::Content(content_index) = self.sorted[sort_index + 1] {
-                if self.content[content_index] < *value { return false; }
-            } else {
-                // TODO: Should we search through unsorted/removed values?
-                return false;
+    }
+
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// The element is moved out of the sorted content while the reference is live,
+    /// and moved back when it is dropped if it is still in order.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_mut(&mut self, index: usize) -> Option<MergeVecRefMut<'_, T, Ix>> {
+        if index < self.len() {
+            // If the reference is leaked, the element is left unsorted
+            self.reclaim_if_full();
+            self.unsort(index);
+            Some(MergeVecRefMut { vec: self, index })
+        } else {
+            None
+        }
+    }
+
+    // would the value at the given content index be in order at the given position in the sort order?
+    // unsorted and removed entries are skipped over to find the neighbouring content
+    fn would_be_sorted(&self, sort_index: usize, index: usize, value: &T) -> bool {
+        match self.nearest_content(self.sorted[..sort_index].iter().rev()) {
+            Some(Some(content_index)) => {
+                self.counters.compared(1);
+                if compare_content(self.stable, &self.content[content_index], content_index, value, index) == Ordering::Greater { return false; }
+            }
+            Some(None) => {}
+            None => return false,
+        }
+        match self.nearest_content(self.sorted[sort_index + 1..].iter()) {
+            Some(Some(content_index)) => {
+                self.counters.compared(1);
+                if compare_content(self.stable, value, index, &self.content[content_index], content_index) == Ordering::Greater { return false; }
+            }
+            Some(None) => {}
+            None => return false,
+        }
+        true
+    }
+
+    // the content index of the first content entry of the sort order,
+    // `Some(None)` if there is no content entry, or `None` if the search distance is exceeded
+    fn nearest_content<'a, I>(&self, targets: I) -> Option<Option<usize>> where I: Iterator<Item=&'a SortTarget<Ix>> {
+        for (skipped, target) in targets.enumerate() {
+            if let SortTarget::Content(content_index) = *target {
+                return Some(Some(content_index.to_usize()));
+            } else if skipped == self.search_distance {
+                return None;
             }
         }
-        return true;
+        Some(None)
     }
 
     
@@ -185,11 +524,11 @@ impl<T> MergeVec<T> where T: Ord {
 
         // remove all linked values
         for index in len..self.content.len() {
-            let sort_index = self.sort_index[index];
+            let sort_index = self.sort_index[index].to_usize();
             match self.sorted[sort_index] {
                 SortTarget::Content(_) => self.sorted[sort_index] = SortTarget::Removed,
                 SortTarget::Unsorted(unsort_index) => {
-                    self.unsorted[unsort_index] = None;
+                    self.unsorted[unsort_index.to_usize()] = None;
                     self.sorted[sort_index] = SortTarget::Removed;
                 },
                 SortTarget::Removed => {}
@@ -198,67 +537,153 @@ impl<T> MergeVec<T> where T: Ord {
         //truncate data
         self.content.truncate(len);
         self.sort_index.truncate(len);
+        if self.sorted.len() > 2 * self.content.len() {
+            // most of the sort order is holes
+            self.compact();
+        }
     }
-    
-    /// Create a new, empty vector.
-    pub fn new() -> MergeVec<T> {
-        MergeVec {
-            content: Vec::new(),
-            sort_index: Vec::new(),
-            sorted: Vec::new(),
-            unsorted: Vec::new(),
+
+    /// Reclaim the space in the sort order used by removed elements.
+    /// This takes O(n) time, and happens automatically on sorting,
+    /// or on truncating when most of the sort order is removed elements.
+    pub fn compact(&mut self) {
+        let mut sort_len = 0;
+        let mut new_unsorted = Vec::new();
+        for sort_index in 0..self.sorted.len() {
+            let (index, target) = match self.sorted[sort_index] {
+                SortTarget::Content(index) => (index.to_usize(), SortTarget::Content(index)),
+                SortTarget::Unsorted(unsort_index) => match self.unsorted[unsort_index.to_usize()] {
+                    Some(index) => {
+                        new_unsorted.push(Some(index));
+                        (index.to_usize(), SortTarget::Unsorted(Ix::from_usize(new_unsorted.len() - 1)))
+                    }
+                    None => unreachable!("unsorted entries refer to content"),
+                },
+                SortTarget::Removed => continue,
+            };
+            self.sort_index[index] = Ix::from_usize(sort_len);
+            self.sorted[sort_len] = target;
+            sort_len += 1;
         }
+        self.sorted.truncate(sort_len);
+        self.unsorted = new_unsorted;
+    }
+
+    // reclaim removed entries before a new position in the sort order or the unsorted content
+    // would no longer fit in the index type, which must happen before an element is pushed
+    // or moved out of the sorted content, while every element has its own sort target
+    fn reclaim_if_full(&mut self) {
+        if self.sorted.len() > Ix::MAX || self.unsorted.len() > Ix::MAX {
+            self.compact();
+        }
+    }
+    
+    /// Reserve space for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        self.content.reserve(additional);
+        self.sort_index.reserve(additional);
+        self.sorted.reserve(additional);
+    }
+
+    /// Shrink the space used by the vector as much as possible.
+    /// This frees the buffers kept between sorts, so the next sort allocates again.
+    pub fn shrink_to_fit(&mut self) {
+        self.content.shrink_to_fit();
+        self.sort_index.shrink_to_fit();
+        self.sorted.shrink_to_fit();
+        self.spare_sorted = Vec::new();
+        self.unsorted.shrink_to_fit();
+        self.edited.shrink_to_fit();
+    }
+
+    /// Are equal elements sorted by their index?
+    pub fn is_stable(&self) -> bool {
+        self.stable
+    }
+
+    /// The number of unsorted or removed entries skipped over
+    /// when looking for the sorted neighbours of an element.
+    pub fn search_distance(&self) -> usize {
+        self.search_distance
+    }
+
+    /// Set the number of unsorted or removed entries skipped over
+    /// when looking for the sorted neighbours of an element.
+    /// A larger distance keeps more elements sorted, at the cost of slower updates.
+    pub fn set_search_distance(&mut self, distance: usize) {
+        self.search_distance = distance;
     }
 
     /// Consolidate incremental data, in preparation of producing a sorted iterator
     pub fn sort(&mut self) {
-        sort_new_indexes(&mut self.unsorted, &self.content);
-        let mut new_sort = Vec::with_capacity(self.content.len());
-        {
-            let mut a_iter = ContentIter { index: 0, vec: &self.sorted};
-            let mut b_iter = OpContentIter { index: 0, vec: &self.unsorted};
-            let mut op_a = a_iter.next();
-            let mut op_b = b_iter.next();
-            // merge
-            loop {
-                if let Some(index_a) = op_a {
-                if let Some(index_b) = op_b {
-                    if self.content[index_a] <= self.content[index_b] {
-                        self.sort_index[index_a] = new_sort.len();
-                        new_sort.push(SortTarget::Content(index_a));
-                        op_a = a_iter.next();
-                    } else {
-                        self.sort_index[index_b] = new_sort.len();
-                        new_sort.push(SortTarget::Content(index_b));
-                        op_b = b_iter.next();
-                    }
-                } else { break; }
-                } else { break; }
+        // move back edited values which are still in order
+        for i in 0..self.edited.len() {
+            let index = self.edited[i].to_usize();
+            if index < self.content.len() {
+                if let SortTarget::Unsorted(_) = self.sorted[self.sort_index[index].to_usize()] {
+                    self.update_sort_target(index);
+                }
             }
-            // add data from longer iter
-            while let Some(index_a) = op_a {
-                self.sort_index[index_a] = new_sort.len();
-                new_sort.push(SortTarget::Content(index_a));
-                op_a = a_iter.next();
+        }
+        self.edited.clear();
+        if self.unsorted.iter().all(Option::is_none) {
+            // every element is already in sorted position
+            if self.sorted.len() == self.content.len() {
+                self.unsorted.clear();
+            } else {
+                self.compact();
             }
-            while let Some(index_b) = op_b {
-                self.sort_index[index_b] = new_sort.len();
-                new_sort.push(SortTarget::Content(index_b));
-                op_b = b_iter.next();
+            self.counters.skipped_sort();
+            return;
+        }
+        let mut comparisons = sort_new_indexes(&mut self.unsorted, &self.content, self.stable);
+        let mut new_sort = mem::take(&mut self.spare_sorted);
+        new_sort.clear();
+        new_sort.reserve(self.content.len());
+        // the sorted content keeps its order at the start of the new sort order
+        new_sort.extend(ContentIter { index: 0, vec: &self.sorted }.map(SortTarget::Content));
+        if new_sort.is_empty() {
+            self.counters.full_sort();
+        }
+        // merge from the back, galloping past the sorted content greater than each new element
+        let first_new = self.unsorted.partition_point(Option::is_none);
+        let mut content_len = new_sort.len();
+        new_sort.resize(content_len + self.unsorted.len() - first_new, SortTarget::Removed);
+        for (new_len, new_index) in self.unsorted[first_new..].iter().enumerate().rev() {
+            if let Some(new_index) = *new_index {
+                let index = new_index.to_usize();
+                let position = gallop_back(&new_sort[..content_len], &self.content, self.stable, index, &mut comparisons);
+                new_sort.copy_within(position..content_len, position + new_len + 1);
+                new_sort[position + new_len] = SortTarget::Content(new_index);
+                content_len = position;
             }
         }
-        //finalize vecs
-        self.sorted = new_sort;
-        self.unsorted = Vec::new();
+        for (sort_index, target) in new_sort.iter().enumerate() {
+            if let SortTarget::Content(index) = *target {
+                self.sort_index[index.to_usize()] = Ix::from_usize(sort_index);
+            }
+        }
+        self.counters.compared(comparisons);
+        //finalize vecs, keeping the old sort order to reuse next time
+        self.spare_sorted = mem::replace(&mut self.sorted, new_sort);
+        self.unsorted.clear();
 
     }
 
-    pub fn sorted_iter(&mut self) -> MergeVecIter<T> {
+    pub fn sorted_iter(&mut self) -> MergeVecIter<'_, T, Ix> {
         self.sort();
         MergeVecIter {
-            index: 0,
             content: &self.content,
-            sort_order: &self.sorted,
+            sort_order: self.sorted.iter(),
+        }
+    }
+
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> MergeVecIndexedIter<'_, T, Ix> {
+        self.sort();
+        MergeVecIndexedIter {
+            content: &self.content,
+            sort_order: self.sorted.iter(),
         }
     }
 
@@ -267,20 +692,148 @@ impl<T> MergeVec<T> where T: Ord {
     pub fn get(&self, index: usize) -> Option<&T> {
         self.content.get(index)
     }
+
+    /// The rank of the `i`th element in sorted order.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn rank_of(&mut self, index: usize) -> usize {
+        self.sort();
+        self.sort_index[index].to_usize()
+    }
+
+    /// The `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn nth_sorted(&mut self, k: usize) -> Option<&T> {
+        self.index_of_nth_sorted(k).and_then(move |index| self.content.get(index))
+    }
+
+    /// The index of the `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn index_of_nth_sorted(&mut self, k: usize) -> Option<usize> {
+        self.sort();
+        match self.sorted.get(k) {
+            Some(&SortTarget::Content(index)) => Some(index.to_usize()),
+            _ => None,
+        }
+    }
+
+    /// Binary search the sorted vector for a value.
+    /// Returns `Ok` of its rank if the value is present,
+    /// and `Err` of the rank it would be inserted at if not.
+    pub fn binary_search_sorted(&mut self, value: &T) -> Result<usize, usize> {
+        let sort_index = self.lower_bound(value);
+        match self.sorted.get(sort_index) {
+            Some(&SortTarget::Content(index)) if self.content[index.to_usize()] == *value => Ok(sort_index),
+            _ => Err(sort_index),
+        }
+    }
+
+    /// The rank of the first element not less than `value`.
+    pub fn lower_bound(&mut self, value: &T) -> usize {
+        self.sort();
+        self.partition_point(|other| other < value)
+    }
+
+    /// The rank of the first element greater than `value`.
+    pub fn upper_bound(&mut self, value: &T) -> usize {
+        self.sort();
+        self.partition_point(|other| other <= value)
+    }
+
+    /// A sorted iterator over the elements of the vector in the given range of values.
+    pub fn sorted_range<R>(&mut self, range: R) -> MergeVecIter<'_, T, Ix> where R: RangeBounds<T> {
+        self.sort();
+        let range = range_positions(&range, self.sorted.len(),
+            |value| self.partition_point(|other| other < value),
+            |value| self.partition_point(|other| other <= value));
+        MergeVecIter {
+            content: &self.content,
+            sort_order: self.sorted[range].iter(),
+        }
+    }
+
+    // The position in the sort order of the first element for which `pred` is false,
+    // assuming the vector is sorted.
+    fn partition_point<P>(&self, mut pred: P) -> usize where P: FnMut(&T) -> bool {
+        self.sorted.partition_point(|&target| match target {
+            SortTarget::Content(index) => pred(&self.content[index.to_usize()]),
+            _ => false,
+        })
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.get()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.counters.reset();
+    }
+
+    /// The number of elements displaced from the sort order since the last sort.
+    pub fn displaced_count(&self) -> usize {
+        self.unsorted.iter().filter(|index| index.is_some()).count()
+    }
+
+    /// The number of ascending runs in the order the elements were last sorted in,
+    /// with displaced elements left in place.
+    /// This takes O(n) time, and does not change the sort order.
+    pub fn run_count(&self) -> usize {
+        count_runs(&self.content, &self.presorted_order(), &mut T::cmp)
+    }
+
+    /// The number of pairs of elements which are out of order in the order the elements
+    /// were last sorted in, with displaced elements left in place.
+    /// This takes O(n log n) time, and does not change the sort order.
+    pub fn inversion_count(&self) -> usize {
+        count_inversions(&self.content, &self.presorted_order(), &mut T::cmp)
+    }
+
+    // the content indexes in the order they were last sorted in, with displaced elements left in place
+    fn presorted_order(&self) -> Vec<usize> {
+        self.sorted.iter().filter_map(|&target| match target {
+            SortTarget::Content(index) => Some(index.to_usize()),
+            SortTarget::Unsorted(unsort_index) => self.unsorted[unsort_index.to_usize()].map(Ix::to_usize),
+            SortTarget::Removed => None,
+        }).collect()
+    }
 }
 
-impl<T> From<Vec<T>> for MergeVec<T> where T: Ord + Clone {
+impl<T> From<Vec<T>> for MergeVec<T> where T: Ord {
     fn from(vec: Vec<T>) -> MergeVec<T> {
-        let length = vec.len();
+        MergeVec::from_content(vec, false)
+    }
+}
+
+impl<T, Ix> Default for MergeVec<T, Ix> where T: Ord, Ix: Index {
+    fn default() -> MergeVec<T, Ix> {
         MergeVec {
-            content: vec,
-            sort_index: (0..length).collect(),
-            sorted: (0..length).map(|i| SortTarget::Unsorted(i)).collect(),
-            unsorted: (0..length).map(|i| Some(i)).collect(),
+            content: Vec::new(),
+            sort_index: Vec::new(),
+            sorted: Vec::new(),
+            spare_sorted: Vec::new(),
+            unsorted: Vec::new(),
+            edited: Vec::new(),
+            stable: false,
+            search_distance: DEFAULT_SEARCH_DISTANCE,
+            counters: Counters::default(),
         }
     }
 }
 
+#[cfg(feature = "heapsize")]
+impl<T, Ix> heapsize::HeapSizeOf for MergeVec<T, Ix>
+    where T: heapsize::HeapSizeOf + Ord, Ix: heapsize::HeapSizeOf
+{
+    fn heap_size_of_children(&self) -> usize {
+        self.content.heap_size_of_children() + self.sort_index.heap_size_of_children() +
+        self.sorted.heap_size_of_children() + self.spare_sorted.heap_size_of_children() +
+        self.unsorted.heap_size_of_children() + self.edited.heap_size_of_children()
+    }
+}
+
 #[test]
 fn test_push() {
     let mut vec = MergeVec::new();
@@ -371,3 +924,339 @@ fn test_set() {
     assert_eq!(vec.get(4), None);
     assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &1, &10, &30]);
 }
+
+#[test]
+fn test_rank() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.rank_of(0), 0);
+    assert_eq!(vec.rank_of(1), 3);
+    assert_eq!(vec.rank_of(2), 2);
+    assert_eq!(vec.rank_of(3), 1);
+    assert_eq!(vec.nth_sorted(1), Some(&10));
+    assert_eq!(vec.nth_sorted(4), None);
+    assert_eq!(vec.index_of_nth_sorted(3), Some(1));
+    assert_eq!(vec.index_of_nth_sorted(4), None);
+
+    vec.set(3, 40);
+    assert_eq!(vec.rank_of(3), 3);
+    assert_eq!(vec.rank_of(1), 2);
+    assert_eq!(vec.nth_sorted(1), Some(&20));
+
+    vec.truncate(2);
+    assert_eq!(vec.rank_of(1), 1);
+    assert_eq!(vec.nth_sorted(2), None);
+}
+
+#[test]
+fn test_search() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10, 20]);
+    assert_eq!(vec.binary_search_sorted(&10), Ok(1));
+    assert_eq!(vec.binary_search_sorted(&15), Err(2));
+    assert_eq!(vec.binary_search_sorted(&40), Err(5));
+    assert_eq!(vec.lower_bound(&20), 2);
+    assert_eq!(vec.upper_bound(&20), 4);
+    assert_eq!(vec.sorted_range(&10..&30).collect::<Vec<&usize>>(), vec![&10, &20, &20]);
+    assert_eq!(vec.sorted_range(&10..=&30).collect::<Vec<&usize>>(), vec![&10, &20, &20, &30]);
+    assert_eq!(vec.sorted_range(&25..).collect::<Vec<&usize>>(), vec![&30]);
+    assert_eq!(vec.sorted_range(&30..&10).count(), 0);
+
+    vec.set(0, 25);
+    assert_eq!(vec.sorted_range(&20..&30).collect::<Vec<&usize>>(), vec![&20, &20, &25]);
+}
+
+#[test]
+fn test_iter() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10]);
+    let mut iter = vec.sorted_iter();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(&0));
+    assert_eq!(iter.next_back(), Some(&30));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.nth(1), Some(&20));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(vec.sorted_iter().rev().take(3).collect::<Vec<&usize>>(), vec![&30, &20, &10]);
+    assert_eq!(vec.sorted_iter().skip(2).collect::<Vec<&usize>>(), vec![&20, &30]);
+}
+
+#[test]
+fn test_indexed_iter() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(0, &0), (3, &10), (2, &20), (1, &30)]);
+    vec.set(0, 25);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(3, &10), (2, &20), (0, &25), (1, &30)]);
+}
+
+#[test]
+fn test_get_mut() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &30]);
+
+    *vec.get_mut(2).unwrap() += 1;
+    assert_eq!(vec.get(2), Some(&21));
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &21, &30]);
+
+    *vec.get_mut(2).unwrap() += 10;
+    assert_eq!(vec.get(2), Some(&31));
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &30, &31]);
+
+    assert!(vec.get_mut(4).is_none());
+    if let Some(mut value) = vec.get_mut(0) {
+        *value = 50;
+        ::std::mem::forget(value);
+    }
+    assert_eq!(vec.displaced_count(), 1);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&10, &30, &31, &50]);
+}
+
+#[test]
+fn test_apply_edits() {
+    let mut vec = MergeVec::from(vec![0, 10, 20, 30]);
+    vec.sort();
+    vec.apply_edits(vec![(1, 11), (2, 21), (1, 12)]);
+    assert_eq!(vec.get(1), Some(&12));
+    vec.extend_edits(vec![40, 5]);
+    assert_eq!(vec.len(), 6);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &5, &12, &21, &30, &40]);
+
+    vec.apply_edits(vec![(0, 1), (2, 35)]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&1, &5, &12, &30, &35, &40]);
+    vec.truncate(3);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&1, &12, &35]);
+}
+
+#[test]
+fn test_metrics() {
+    let mut vec = MergeVec::from(vec![0, 10, 20, 30, 40]);
+    assert_eq!(vec.displaced_count(), 0);
+    vec.sort();
+    assert_eq!(vec.displaced_count(), 0);
+    vec.set(1, 35);
+    vec.set(3, 5);
+    assert_eq!(vec.displaced_count(), 2);
+    assert_eq!(vec.run_count(), 3);
+    assert_eq!(vec.inversion_count(), 3);
+    vec.truncate(4);
+    assert_eq!(vec.displaced_count(), 2);
+    assert_eq!(vec.run_count(), 3);
+    assert_eq!(vec.inversion_count(), 3);
+    vec.sort();
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.run_count(), 1);
+    assert_eq!(vec.inversion_count(), 0);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats() {
+    let mut vec = MergeVec::from(vec![0, 30, 20, 10]);
+    vec.sort();
+    assert_eq!(vec.stats().unsorted, 2);
+    assert_eq!(vec.stats().full_sorts, 0);
+    vec.reset_stats();
+    vec.sort();
+    assert_eq!(vec.stats(), Stats { comparisons: 0, skipped_sorts: 1, unsorted: 0, full_sorts: 0 });
+    vec.reset_stats();
+    vec.push(40);
+    vec.set(0, 25);
+    vec.sort();
+    assert_eq!(vec.stats(), Stats { comparisons: 5, skipped_sorts: 0, unsorted: 1, full_sorts: 0 });
+}
+
+#[test]
+fn test_stable() {
+    use permuted_vec::PermutedVec;
+
+    let mut vec = MergeVec::new_stable();
+    assert_eq!(vec.is_stable(), true);
+    for &value in &[1, 0, 1, 0, 1, 2] {
+        vec.push(value);
+    }
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![1, 3, 0, 2, 4, 5]);
+
+    vec.set(4, 0);
+    vec.set(1, 1);
+    vec.set(5, 0);
+    vec.apply_edits(vec![(0, 0), (2, 0)]);
+    vec.push(1);
+    vec.sort();
+    vec.set(3, 1);
+
+    let mut expected = PermutedVec::from((0..vec.len()).map(|index| *vec.get(index).unwrap()).collect::<Vec<usize>>());
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(),
+               expected.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>());
+}
+
+#[test]
+fn test_compact() {
+    let mut vec = MergeVec::from(vec![30, 10, 20, 40]);
+    vec.sort();
+    vec.truncate(2);
+    assert_eq!(vec.sorted.len(), 4);
+    vec.push(50);
+    assert_eq!(vec.sorted.len(), 5);
+    vec.set(0, 15);
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&10, &15, &50]);
+    assert_eq!(vec.sorted.len(), 3);
+
+    for value in 0..100 {
+        vec.push(value);
+    }
+    vec.sort();
+    for _ in 0..10 {
+        vec.truncate(10);
+        for value in 0..10 {
+            vec.push(value + 100);
+        }
+    }
+    assert!(vec.sorted.len() <= 2 * vec.len() + 10);
+    vec.compact();
+    assert_eq!(vec.sorted.len(), vec.len());
+    assert_eq!(vec.sorted_iter().rev().take(2).collect::<Vec<&usize>>(), vec![&109, &108]);
+}
+
+#[test]
+fn test_search_distance() {
+    let mut vec = MergeVec::new();
+    assert_eq!(vec.search_distance(), DEFAULT_SEARCH_DISTANCE);
+    for value in [10, 20, 30, 40, 50] {
+        vec.push(value);
+    }
+    vec.sort();
+    vec.set(1, 100);
+    vec.set(2, 200);
+    assert_eq!(vec.displaced_count(), 2);
+    vec.set(3, 45);
+    assert_eq!(vec.displaced_count(), 2);
+
+    vec.set_search_distance(1);
+    vec.set(3, 40);
+    assert_eq!(vec.displaced_count(), 3);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&10, &40, &50, &100, &200]);
+}
+
+#[test]
+fn test_removed_search_distance() {
+    let mut vec = MergeVec::new();
+    for value in 0..20 {
+        vec.push(value);
+    }
+    vec.sort();
+    vec.truncate(15);
+    assert_eq!(vec.sorted.len(), 20);
+    vec.push(100);
+    assert_eq!(vec.displaced_count(), 0);
+
+    vec.truncate(15);
+    vec.set_search_distance(2);
+    vec.push(100);
+    assert_eq!(vec.displaced_count(), 1);
+    vec.set(14, 50);
+    assert_eq!(vec.displaced_count(), 2);
+    assert_eq!(vec.sorted_iter().rev().take(3).collect::<Vec<&usize>>(), vec![&100, &50, &13]);
+}
+
+#[test]
+fn test_from_sorted_subsequence() {
+    let vec = MergeVec::from(vec![0, 10, 20, 30, 40]);
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.run_count(), 1);
+
+    let mut vec = MergeVec::from(vec![50, 0, 10, 5, 20, 20, 60, 30]);
+    assert_eq!(vec.displaced_count(), 3);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &5, &10, &20, &20, &30, &50, &60]);
+
+    let mut vec = MergeVec::from(Vec::<usize>::new());
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.sorted_iter().len(), 0);
+
+    let mut vec = MergeVec::from_vec_stable(vec![1, 0, 1, 0, 1, 2]);
+    assert_eq!(vec.is_stable(), true);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![1, 3, 0, 2, 4, 5]);
+    vec.set(4, 0);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![1, 3, 4, 0, 2, 5]);
+
+    // Elements need not be cloned, and any index type can be used
+    #[derive(Debug,Eq,Ord,PartialEq,PartialOrd)]
+    struct Unclonable(usize);
+    let mut vec: MergeVec<Unclonable, u16> = MergeVec::from_vec_stable_in(vec![Unclonable(1), Unclonable(0), Unclonable(1)]);
+    assert_eq!(vec.displaced_count(), 1);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![1, 0, 2]);
+}
+
+#[test]
+fn test_capacity() {
+    let mut vec = MergeVec::with_capacity(10);
+    assert!(vec.content.capacity() >= 10);
+    for value in (0..10).rev() {
+        vec.push(value);
+    }
+    vec.sort();
+    vec.set(0, 20);
+    vec.sort();
+    let buffers = (vec.sorted.as_ptr(), vec.spare_sorted.as_ptr());
+    vec.set(0, 5);
+    vec.sort();
+    assert_eq!((vec.spare_sorted.as_ptr(), vec.sorted.as_ptr()), buffers);
+    assert_eq!(vec.sorted_iter().take(3).collect::<Vec<&usize>>(), vec![&0, &1, &2]);
+
+    vec.truncate(3);
+    vec.shrink_to_fit();
+    assert_eq!(vec.spare_sorted.capacity(), 0);
+    vec.reserve(5);
+    assert!(vec.sort_index.capacity() >= 8);
+}
+
+#[test]
+fn test_index_reclaim() {
+    // The removed entries left by truncating would take the sort order past what u16 can hold
+    let mut vec: MergeVec<usize, u16> = MergeVec::new_in();
+    for value in 0..40000 {
+        vec.push(value);
+    }
+    vec.truncate(30000);
+    for value in 30000..65000 {
+        vec.push(65000 - value);
+    }
+    assert_eq!(vec.len(), 65000);
+    assert_eq!(vec.rank_of(30000), 64999);
+    assert_eq!(vec.nth_sorted(64998), Some(&34999));
+    let sorted: Vec<usize> = vec.sorted_iter().cloned().collect();
+    assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[cfg(feature = "heapsize")]
+#[test]
+fn test_index_heapsize() {
+    use heapsize::HeapSizeOf;
+
+    // Sort both vectors, so the spare sort orders are allocated
+    let mut wide = MergeVec::new();
+    wide.extend_edits(vec![0u8; 1000]);
+    wide.sort();
+    let mut narrow: MergeVec<u8, u16> = MergeVec::new_in();
+    narrow.extend_edits(vec![0u8; 1000]);
+    narrow.sort();
+    assert!(narrow.heap_size_of_children() * 2 < wide.heap_size_of_children());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_gallop() {
+    let mut vec = MergeVec::new();
+    for value in 0..1024 {
+        vec.push(value * 2);
+    }
+    vec.sort();
+    vec.set(10, 2001);
+    vec.set(900, 1);
+    vec.set(500, 1001);
+    vec.reset_stats();
+    vec.sort();
+    // sorting three elements and galloping to each
+    assert!(vec.stats().comparisons <= 3 + 3 * 2 * 10);
+    assert_eq!(vec.sorted_iter().take(4).collect::<Vec<&usize>>(), vec![&0, &1, &2, &4]);
+    assert_eq!(vec.rank_of(10), 1000);
+    assert_eq!(vec.rank_of(500), 500);
+}
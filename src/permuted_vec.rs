@@ -1,47 +1,216 @@
 use std::cmp::Ordering;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::{Bound, Range, RangeBounds};
 use std::slice::Iter;
 
+use index::Index;
+#[cfg(feature = "stats")]
+use stats::Stats;
+use stats::Counters;
+
 /// The type of permuted vectors.
-#[derive(Clone,Debug,Eq,PartialEq)]
-pub struct PermutedVec<T> {
+///
+/// The vector remembers which elements have been changed since the permutation
+/// was last sorted, so `resort_by` only compares the changed elements.
+/// `sort_by` checks every element, so can be used with any comparator.
+///
+/// The permutation is stored using the index type `Ix`,
+/// so for example `PermutedVec<T, u32>` uses half the space of
+/// `PermutedVec<T>` on 64-bit platforms, for vectors of up to 2^32 elements.
+#[derive(Clone,Debug)]
+pub struct PermutedVec<T, Ix = usize> {
     // The contents of the vector.
     contents: Vec<T>,
     // The permutation
-    permutation: Vec<usize>,
+    permutation: Vec<Ix>,
+    // The previous permutation, kept to reuse its allocation when sorting
+    spare_permutation: Vec<Ix>,
+    // The ends of the runs being merged, and of the runs they merge into,
+    // kept to reuse their allocations when sorting
+    run_ends: Vec<usize>,
+    spare_run_ends: Vec<usize>,
+    // The changed elements being repaired, kept to reuse its allocation when sorting
+    repaired: Vec<Ix>,
+    // The inverse permutation
+    inverse: Vec<Ix>,
+    // How each element has changed since the last sort
+    changes: Vec<Change>,
+    // The elements which have changed since the last sort
+    changed: Vec<Ix>,
+    // The number of changed elements which may be out of order
+    dirty_count: usize,
+    // Was the permutation last sorted by `sort_by`, rather than `resort_by`?
+    sorted_by_other: bool,
+    // Counters of the work done
+    counters: Counters,
+}
+
+/// The strategies a permuted vector can use to sort its permutation.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum SortStrategy {
+    /// Sort the whole permutation, which suits many scattered edits.
+    Full,
+    /// Take the displaced elements out of the permutation, sort them,
+    /// and insert them back by binary search, which suits a few displaced elements.
+    Repair,
+    /// Merge the ascending runs of the permutation, which suits elements pushed in order.
+    Merge,
+}
+
+// How an element of a permuted vector has changed since the last sort.
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+enum Change {
+    // The element is unchanged.
+    Unchanged,
+    // The element has changed, and may be out of order.
+    Changed,
+    // The element has changed, but is known to be in order.
+    Restored,
+}
+
+#[cfg(feature = "heapsize")]
+impl heapsize::HeapSizeOf for Change {
+    fn heap_size_of_children(&self) -> usize {
+        0
+    }
 }
 
 /// The type of permuted iterators over a permuted vector.
 #[derive(Clone,Debug)]
-pub struct PermutedIter<'a, T> where T: 'a {
-    // Where are we in the iterator
-    index: usize,
+pub struct PermutedIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
     // The contents of the iterator
     contents: &'a[T],
-    // The permutation
-    permutation: &'a[usize],
+    // The remaining permutation
+    permutation: Iter<'a, Ix>,
 }
 
-impl<'a, T> Iterator for PermutedIter<'a, T> where T: 'a {
+impl<'a, T, Ix> Iterator for PermutedIter<'a, T, Ix> where T: 'a, Ix: Index {
     type Item = &'a T;
     fn next(&mut self) -> Option<&'a T> {
-        let sorted_index = self.index;
-        self.index = self.index + 1;
-        self.permutation.get(sorted_index).and_then(|&index| self.contents.get(index))
+        let contents = self.contents;
+        self.permutation.next().map(|&index| &contents[index.to_usize()])
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.permutation.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth(n).map(|&index| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, Ix> DoubleEndedIterator for PermutedIter<'a, T, Ix> where T: 'a, Ix: Index {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.next_back().map(|&index| &contents[index.to_usize()])
+    }
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth_back(n).map(|&index| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, Ix> ExactSizeIterator for PermutedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+impl<'a, T, Ix> FusedIterator for PermutedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+/// The type of permuted iterators over a permuted vector,
+/// which yield each element together with its index in the vector.
+#[derive(Clone,Debug)]
+pub struct PermutedIndexedIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
+    // The contents of the iterator
+    contents: &'a[T],
+    // The remaining permutation
+    permutation: Iter<'a, Ix>,
+}
+
+impl<'a, T, Ix> PermutedIndexedIter<'a, T, Ix> where T: 'a, Ix: Index {
+    // An iterator over the contents, in the order given by the permutation
+    pub(crate) fn new(contents: &'a[T], permutation: &'a[Ix]) -> PermutedIndexedIter<'a, T, Ix> {
+        PermutedIndexedIter {
+            contents,
+            permutation: permutation.iter(),
+        }
+    }
+}
+
+impl<'a, T, Ix> Iterator for PermutedIndexedIter<'a, T, Ix> where T: 'a, Ix: Index {
+    type Item = (usize, &'a T);
+    fn next(&mut self) -> Option<(usize, &'a T)> {
+        let contents = self.contents;
+        self.permutation.next().map(|&index| (index.to_usize(), &contents[index.to_usize()]))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.permutation.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<(usize, &'a T)> {
+        let contents = self.contents;
+        self.permutation.nth(n).map(|&index| (index.to_usize(), &contents[index.to_usize()]))
+    }
+}
+
+impl<'a, T, Ix> DoubleEndedIterator for PermutedIndexedIter<'a, T, Ix> where T: 'a, Ix: Index {
+    fn next_back(&mut self) -> Option<(usize, &'a T)> {
+        let contents = self.contents;
+        self.permutation.next_back().map(|&index| (index.to_usize(), &contents[index.to_usize()]))
+    }
+    fn nth_back(&mut self, n: usize) -> Option<(usize, &'a T)> {
+        let contents = self.contents;
+        self.permutation.nth_back(n).map(|&index| (index.to_usize(), &contents[index.to_usize()]))
     }
 }
 
+impl<'a, T, Ix> ExactSizeIterator for PermutedIndexedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+impl<'a, T, Ix> FusedIterator for PermutedIndexedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
 impl<T> PermutedVec<T> {
+    /// Create a new, empty permuted vector.
+    pub fn new() -> PermutedVec<T> {
+        PermutedVec::new_in()
+    }
+
+    /// Create a new, empty permuted vector, with space for `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> PermutedVec<T> {
+        PermutedVec::with_capacity_in(capacity)
+    }
+}
+
+impl<T, Ix> PermutedVec<T, Ix> where Ix: Index {
+    /// Create a new, empty permuted vector,
+    /// for any index type, for example `PermutedVec::<T, u32>::new_in()`.
+    pub fn new_in() -> PermutedVec<T, Ix> {
+        PermutedVec::default()
+    }
+
+    /// Create a new, empty permuted vector, with space for `capacity` elements,
+    /// for any index type.
+    pub fn with_capacity_in(capacity: usize) -> PermutedVec<T, Ix> {
+        let mut result = PermutedVec::new_in();
+        result.reserve(capacity);
+        result
+    }
+
     /// The length of the vector.
     pub fn len(&self) -> usize {
         self.contents.len()
     }
 
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
     /// Append an element to the end of the vector.
     pub fn push(&mut self, value: T) {
         //println!("vec push index {:?}", self.len());
         let index = self.contents.len();
         self.contents.push(value);
-        self.permutation.push(index);
+        self.permutation.push(Ix::from_usize(index));
+        self.inverse.push(Ix::from_usize(index));
+        self.changes.push(Change::Unchanged);
+        self.mark_dirty(index);
     }
 
     
@@ -50,41 +219,201 @@ impl<T> PermutedVec<T> {
     pub fn set(&mut self, index: usize, value: T) {
         //println!("vec set index {:?}", index);
         self.contents[index] = value;
+        self.mark_dirty(index);
     }
 
-    
-    /// Truncate this vector and reset the sort if necessary.
+    // Remember that the `i`th element has changed since the last sort.
+    pub(crate) fn mark_dirty(&mut self, index: usize) {
+        match self.changes[index] {
+            Change::Unchanged => self.changed.push(Ix::from_usize(index)),
+            Change::Changed => return,
+            Change::Restored => {}
+        }
+        self.changes[index] = Change::Changed;
+        self.dirty_count += 1;
+    }
+
+    // Remember that the `i`th element is in order, even though it has changed,
+    // which is only safe if it is in order with its unchanged neighbours.
+    pub(crate) fn mark_clean(&mut self, index: usize) {
+        if self.changes[index] == Change::Changed {
+            self.changes[index] = Change::Restored;
+            self.dirty_count -= 1;
+        }
+    }
+
+    // Forget that any element has changed since the last sort,
+    // which is only safe if the permutation is sorted.
+    // This takes time proportional to the number of changed elements.
+    pub(crate) fn mark_sorted(&mut self) {
+        for &index in &self.changed {
+            self.changes[index.to_usize()] = Change::Unchanged;
+        }
+        self.changed.clear();
+        self.dirty_count = 0;
+        self.sorted_by_other = false;
+    }
+
+    // Has the `i`th element changed since the last sort, so may be out of order?
+    pub(crate) fn is_dirty(&self, index: usize) -> bool {
+        self.changes[index] == Change::Changed
+    }
+
+    // Has no element changed since the last sort?
+    pub(crate) fn is_clean(&self) -> bool {
+        self.dirty_count == 0
+    }
+
+    // Recompute the list of changed elements from how each element has changed,
+    // after the indexes of the elements have moved.
+    fn reset_changed(&mut self) {
+        let changes = &self.changes;
+        self.changed = (0..changes.len()).filter(|&index| changes[index] != Change::Unchanged).map(Ix::from_usize).collect();
+        self.dirty_count = changes.iter().filter(|&&change| change == Change::Changed).count();
+    }
+
+    // Recompute the inverse permutation from the permutation.
+    fn reset_inverse(&mut self) {
+        self.inverse.resize(self.permutation.len(), Ix::from_usize(0));
+        for (permuted, &index) in self.permutation.iter().enumerate() {
+            self.inverse[index.to_usize()] = Ix::from_usize(permuted);
+        }
+    }
+
+    /// Set many elements of the vector at once.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        for (index, value) in edits {
+            self.set(index, value);
+        }
+    }
+
+    /// Append many elements to the end of the vector at once.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        for value in values {
+            self.push(value);
+        }
+    }
+
+    /// Truncate this vector.
+    /// The permutation of the remaining elements is preserved.
     pub fn truncate(&mut self, len: usize) {
         //println!("vec truncate to {:?}", len);
         if len < self.len() {
             self.contents.truncate(len);
-            self.permutation.clear();
-            self.permutation.extend(0..len);
+            self.permutation.retain(|&index| index.to_usize() < len);
+            self.inverse.truncate(len);
+            self.reset_inverse();
+            self.changes.truncate(len);
+            self.reset_changed();
         }
     }
     
-    /// Create a new, empty presorted vector.
-    pub fn new() -> PermutedVec<T> {
-        PermutedVec {
-            contents: Vec::new(),
-            permutation: Vec::new(),
+    /// Remove and return the `i`th element of the vector,
+    /// shifting all elements after it down by one.
+    /// The permutation of the remaining elements is preserved.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn remove(&mut self, index: usize) -> T {
+        let value = self.contents.remove(index);
+        self.changes.remove(index);
+        self.permutation.retain(|&other| other.to_usize() != index);
+        for other in &mut self.permutation {
+            if other.to_usize() > index { *other = Ix::from_usize(other.to_usize() - 1); }
+        }
+        self.inverse.pop();
+        self.reset_inverse();
+        self.reset_changed();
+        value
+    }
+
+    /// Remove and return the `i`th element of the vector,
+    /// replacing it by the last element of the vector.
+    /// The permutation of the remaining elements is preserved.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        let value = self.contents.swap_remove(index);
+        self.changes.swap_remove(index);
+        let last = self.contents.len();
+        self.permutation.retain(|&other| other.to_usize() != index);
+        for other in &mut self.permutation {
+            if other.to_usize() == last { *other = Ix::from_usize(index); }
+        }
+        self.inverse.pop();
+        self.reset_inverse();
+        self.reset_changed();
+        value
+    }
+
+    /// Insert an element as the `i`th element of the vector,
+    /// shifting all elements after it up by one.
+    /// The new element is placed at the end of the permutation.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let permuted = self.len();
+        self.insert_permuted(index, permuted, value);
+    }
+
+    // Insert an element as the `i`th element of the vector,
+    // and as the `j`th element of the permutation.
+    pub(crate) fn insert_permuted(&mut self, index: usize, permuted: usize, value: T) {
+        self.contents.insert(index, value);
+        for other in &mut self.permutation {
+            if other.to_usize() >= index { *other = Ix::from_usize(other.to_usize() + 1); }
         }
+        self.permutation.insert(permuted, Ix::from_usize(index));
+        self.reset_inverse();
+        self.changes.insert(index, Change::Unchanged);
+        self.reset_changed();
+        self.mark_dirty(index);
+    }
+
+    /// Reserve space for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        self.contents.reserve(additional);
+        self.permutation.reserve(additional);
+        self.inverse.reserve(additional);
+        self.changes.reserve(additional);
+    }
+
+    /// Shrink the space used by the vector as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.contents.shrink_to_fit();
+        self.permutation.shrink_to_fit();
+        self.spare_permutation = Vec::new();
+        self.run_ends = Vec::new();
+        self.spare_run_ends = Vec::new();
+        self.repaired = Vec::new();
+        self.inverse.shrink_to_fit();
+        self.changes.shrink_to_fit();
+        self.changed.shrink_to_fit();
     }
 
     /// An iterator over the permutation
-    pub fn permutation_iter(&self) -> Iter<usize> {
+    pub fn permutation_iter(&self) -> Iter<'_, Ix> {
         self.permutation.iter()
     }
 
     /// An iterator over the permuted vector
-    pub fn permuted_iter(&self) -> PermutedIter<T> {
+    pub fn permuted_iter(&self) -> PermutedIter<'_, T, Ix> {
+        PermutedIter {
+            contents: &self.contents,
+            permutation: self.permutation.iter(),
+        }
+    }
+
+    // An iterator over part of the permuted vector
+    pub(crate) fn permuted_range_iter(&self, range: Range<usize>) -> PermutedIter<'_, T, Ix> {
         PermutedIter {
-            index: 0,
             contents: &self.contents,
-            permutation: &self.permutation,
+            permutation: self.permutation[range].iter(),
         }
     }
 
+    /// An iterator over the permuted vector, together with the index of each element
+    pub fn permuted_indexed_iter(&self) -> PermutedIndexedIter<'_, T, Ix> {
+        PermutedIndexedIter::new(&self.contents, &self.permutation)
+    }
+
     /// Is the permuted vector already sorted?
     pub fn is_sorted_by<F>(&self, f: &mut F) -> bool where F: FnMut(&T, &T) -> Ordering {
         let iter_1 = self.permuted_iter();
@@ -93,53 +422,543 @@ impl<T> PermutedVec<T> {
         iter_1.zip(iter_2).all(|(value_1, value_2)| f(value_1, value_2) != Ordering::Greater)
     }
 
-    /// Sort the permutation on the vector
+    /// Sort the permutation on the vector.
+    /// This checks every element, so the comparator need not be the one
+    /// the permutation was last sorted by.
     pub fn sort_by<F>(&mut self, mut f: F) where F: FnMut(&T, &T) -> Ordering {
-        if !self.is_sorted_by(&mut f) {
-            let contents = &self.contents;
-            self.permutation.sort_by(|&index_1, &index_2|
-                match f(&contents[index_1], &contents[index_2]) {
-                    Ordering::Equal => index_1.cmp(&index_2),
-                    ord => ord,
+        let mut comparisons = 0;
+        {
+            let mut counted = |value_1: &T, value_2: &T| { comparisons += 1; f(value_1, value_2) };
+            if self.is_sorted_by(&mut counted) {
+                self.counters.skipped_sort();
+            } else {
+                self.merge_runs_by(&mut counted);
+            }
+        }
+        self.counters.compared(comparisons);
+        self.mark_sorted();
+        self.sorted_by_other = true;
+    }
+
+    /// Sort the permutation on the vector again, by the comparator used by the last `resort_by`.
+    /// Only the elements changed since the last sort are compared with their neighbours,
+    /// so if nothing has changed this takes O(1) time, and if k elements have changed
+    /// they are sorted and merged back into the permutation with O(k log n) comparisons.
+    /// If the vector was last sorted by `sort_by`, every element is checked.
+    pub fn resort_by<F>(&mut self, mut f: F) where F: FnMut(&T, &T) -> Ordering {
+        if self.is_clean() && !self.sorted_by_other {
+            // Nothing has changed since the last sort
+            self.counters.skipped_sort();
+            return;
+        }
+        let mut comparisons = 0;
+        {
+            let mut counted = |value_1: &T, value_2: &T| { comparisons += 1; f(value_1, value_2) };
+            if self.sorted_by_other || 2 * self.dirty_count > self.len() {
+                if self.is_sorted_by(&mut counted) {
+                    self.counters.skipped_sort();
+                } else {
+                    self.merge_runs_by(&mut counted);
+                }
+            } else if self.dirty_is_sorted_by(&mut counted) {
+                self.counters.skipped_sort();
+            } else {
+                self.repair_by(&mut counted);
+            }
+        }
+        self.counters.compared(comparisons);
+        self.mark_sorted();
+    }
+
+    // Sort the permutation on the vector using the given strategy,
+    // if the changed elements are out of order.
+    pub(crate) fn sort_with_by<F>(&mut self, strategy: SortStrategy, mut f: F) where F: FnMut(&T, &T) -> Ordering {
+        let mut comparisons = 0;
+        {
+            let mut counted = |value_1: &T, value_2: &T| { comparisons += 1; f(value_1, value_2) };
+            let sorted = if self.sorted_by_other {
+                self.is_sorted_by(&mut counted)
+            } else {
+                self.dirty_is_sorted_by(&mut counted)
+            };
+            if sorted {
+                self.counters.skipped_sort();
+            } else {
+                match strategy {
+                    SortStrategy::Full => self.full_sort_by(&mut counted),
+                    SortStrategy::Repair => self.repair_by(&mut counted),
+                    SortStrategy::Merge => self.merge_runs_by(&mut counted),
+                }
+            }
+        }
+        self.counters.compared(comparisons);
+        self.mark_sorted();
+    }
+
+    // Sort the permutation on the vector by integer keys of `bytes` bytes,
+    // if the changed elements are out of order. A few changed elements are repaired,
+    // and otherwise the permutation is rebuilt by an LSD radix sort of the keys.
+    // Ties are broken by index, as in a full sort.
+    pub(crate) fn radix_sort_by_key<F>(&mut self, bytes: usize, key: F) where F: Fn(&T) -> u64 {
+        let len = self.len();
+        let log_len = (usize::BITS - len.leading_zeros()) as usize;
+        let mut comparisons = 0;
+        {
+            let mut counted = |value_1: &T, value_2: &T| { comparisons += 1; key(value_1).cmp(&key(value_2)) };
+            let sorted = if self.sorted_by_other {
+                self.is_sorted_by(&mut counted)
+            } else {
+                self.dirty_is_sorted_by(&mut counted)
+            };
+            if sorted {
+                self.counters.skipped_sort();
+            } else if !self.sorted_by_other && self.dirty_count * log_len <= len {
+                // Repairing the changed elements takes fewer steps than a radix sort
+                self.repair_by(&mut counted);
+            } else {
+                self.radix_sort(bytes, &key);
+            }
+        }
+        self.counters.compared(comparisons);
+        self.mark_sorted();
+    }
+
+    // Rebuild the permutation by an LSD radix sort of integer keys of `bytes` bytes.
+    fn radix_sort<F>(&mut self, bytes: usize, key: &F) where F: Fn(&T) -> u64 {
+        self.counters.full_sort();
+        let keys: Vec<u64> = self.contents.iter().map(key).collect();
+        for (permuted, index) in self.permutation.iter_mut().enumerate() {
+            *index = Ix::from_usize(permuted);
+        }
+        let mut buffer = mem::take(&mut self.spare_permutation);
+        buffer.clear();
+        buffer.extend_from_slice(&self.permutation);
+        for byte in 0..bytes {
+            let digit = |index: Ix| ((keys[index.to_usize()] >> (8 * byte)) & 0xff) as usize;
+            let mut counts = [0; 256];
+            for &index in &self.permutation {
+                counts[digit(index)] += 1;
+            }
+            if counts.contains(&keys.len()) {
+                // Every key has the same digit
+                continue;
+            }
+            let mut start = 0;
+            for count in counts.iter_mut() {
+                start += mem::replace(count, start);
+            }
+            for &index in &self.permutation {
+                let digit = digit(index);
+                buffer[counts[digit]] = index;
+                counts[digit] += 1;
+            }
+            mem::swap(&mut self.permutation, &mut buffer);
+        }
+        self.spare_permutation = buffer;
+        self.reset_inverse();
+    }
+
+    // Sort the whole permutation, without looking for runs.
+    fn full_sort_by<F>(&mut self, f: &mut F) where F: FnMut(&T, &T) -> Ordering {
+        self.counters.full_sort();
+        let contents = &self.contents;
+        self.permutation.sort_unstable_by(|&index_1, &index_2| compare_indexes(contents, f, index_1, index_2));
+        self.reset_inverse();
+    }
+
+    // Sort the whole permutation by finding its ascending runs and merging them in pairs,
+    // which takes O(n log r) comparisons for a permutation with r runs.
+    fn merge_runs_by<F>(&mut self, f: &mut F) where F: FnMut(&T, &T) -> Ordering {
+        self.counters.full_sort();
+        let contents = &self.contents;
+        let len = self.permutation.len();
+        // The end of each run
+        let mut ends = mem::take(&mut self.run_ends);
+        ends.clear();
+        {
+            let permutation = &self.permutation;
+            ends.extend((1..len).filter(|&permuted| compare_indexes(contents, f, permutation[permuted - 1], permutation[permuted]) == Ordering::Greater));
+        }
+        ends.push(len);
+        let mut merged_ends = mem::take(&mut self.spare_run_ends);
+        let mut buffer = mem::take(&mut self.spare_permutation);
+        buffer.clear();
+        buffer.reserve(len);
+        while ends.len() > 1 {
+            let mut start = 0;
+            merged_ends.clear();
+            for pair in ends.chunks(2) {
+                if pair.len() == 2 {
+                    merge_into(contents, f, &self.permutation[start..pair[0]], &self.permutation[pair[0]..pair[1]], &mut buffer);
+                } else {
+                    buffer.extend_from_slice(&self.permutation[start..pair[0]]);
                 }
+                start = pair[pair.len() - 1];
+                merged_ends.push(start);
+            }
+            mem::swap(&mut self.permutation, &mut buffer);
+            buffer.clear();
+            mem::swap(&mut ends, &mut merged_ends);
+        }
+        self.run_ends = ends;
+        self.spare_run_ends = merged_ends;
+        self.spare_permutation = buffer;
+        self.reset_inverse();
+    }
+
+    // Are the changed elements in order with their neighbours in the permutation?
+    // As the unchanged elements are still in order, this is whether the permutation is sorted.
+    // This takes time proportional to the number of changed elements.
+    pub(crate) fn dirty_is_sorted_by<F>(&self, f: &mut F) -> bool where F: FnMut(&T, &T) -> Ordering {
+        if self.is_clean() {
+            return true;
+        }
+        let contents = &self.contents;
+        let permutation = &self.permutation;
+        self.changed.iter().all(|&index| {
+            let index = index.to_usize();
+            if !self.is_dirty(index) {
+                return true;
+            }
+            let permuted = self.inverse[index].to_usize();
+            // The pair before a changed element is checked with that element
+            let before = permuted.checked_sub(1).map(|before| permutation[before].to_usize());
+            let after = permutation.get(permuted + 1).map(|&after| after.to_usize());
+            before.map(|before| self.is_dirty(before) || f(&contents[before], &contents[index]) != Ordering::Greater).unwrap_or(true) &&
+            after.map(|after| f(&contents[index], &contents[after]) != Ordering::Greater).unwrap_or(true)
+        })
+    }
+
+    // Sort the permutation by taking out the changed elements, sorting them,
+    // and merging them back into the unchanged elements, which are still sorted.
+    fn repair_by<F>(&mut self, f: &mut F) where F: FnMut(&T, &T) -> Ordering {
+        let contents = &self.contents;
+        let changes = &self.changes;
+        let mut changed = mem::take(&mut self.repaired);
+        changed.clear();
+        changed.extend(self.changed.iter().cloned().filter(|&index| changes[index.to_usize()] == Change::Changed));
+        changed.sort_by(|&index_1, &index_2| compare_indexes(contents, f, index_1, index_2));
+        self.permutation.retain(|&index| changes[index.to_usize()] != Change::Changed);
+        let mut new_permutation = mem::take(&mut self.spare_permutation);
+        new_permutation.clear();
+        new_permutation.reserve(contents.len());
+        let unchanged = mem::replace(&mut self.permutation, new_permutation);
+        let mut start = 0;
+        for &index in &changed {
+            // Binary search the unchanged elements after the previous insertion
+            let end = start + unchanged[start..].partition_point(|&other|
+                compare_indexes(contents, f, other, index) == Ordering::Less
             );
-            debug_assert!(self.is_sorted_by(&mut f));
+            self.permutation.extend_from_slice(&unchanged[start..end]);
+            self.permutation.push(index);
+            start = end;
         }
+        self.permutation.extend_from_slice(&unchanged[start..]);
+        self.spare_permutation = unchanged;
+        self.repaired = changed;
+        self.reset_inverse();
     }
 
     /// A sorted iterator over the vector.
-    pub fn sorted_iter_by<F>(&mut self, f: F) -> PermutedIter<T> where F: FnMut(&T, &T) -> Ordering {
+    pub fn sorted_iter_by<F>(&mut self, f: F) -> PermutedIter<'_, T, Ix> where F: FnMut(&T, &T) -> Ordering {
         self.sort_by(f);
         self.permuted_iter()
     }
 
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter_by<F>(&mut self, f: F) -> PermutedIndexedIter<'_, T, Ix> where F: FnMut(&T, &T) -> Ordering {
+        self.sort_by(f);
+        self.permuted_indexed_iter()
+    }
+
     /// Get the `i`th element of the vector.
     /// Returns `None` if the vector contains fewer than `i` elements.
     pub fn get(&self, index: usize) -> Option<&T> {
         self.contents.get(index)
     }
 
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    /// The element is treated as changed, whether or not it is mutated.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len() {
+            self.mark_dirty(index);
+        }
+        self.contents.get_mut(index)
+    }
+
     /// Get the `i`th element of the permuted vector.
     /// Returns `None` if the vector contains fewer than `i` elements.
     pub fn get_permuted(&self, permuted: usize) -> Option<&T> {
-        self.permutation.get(permuted).and_then(|&index| self.contents.get(index))
+        self.permutation.get(permuted).and_then(|&index| self.contents.get(index.to_usize()))
+    }
+
+    /// The position of the `i`th element in the permutation,
+    /// which is its rank in sorted order after `sort_by`.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn rank_of(&self, index: usize) -> usize {
+        self.inverse[index].to_usize()
+    }
+
+    // The inverse permutation.
+    #[cfg(feature = "serde")]
+    pub(crate) fn inverse(&self) -> &[Ix] {
+        &self.inverse
+    }
+
+    /// The `k`th element of the permutation,
+    /// which is the `k`th smallest element after `sort_by`.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn nth_sorted(&self, k: usize) -> Option<&T> {
+        self.get_permuted(k)
+    }
+
+    /// The index of the `k`th element of the permutation,
+    /// which is the index of the `k`th smallest element after `sort_by`.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn index_of_nth_sorted(&self, k: usize) -> Option<usize> {
+        self.permutation.get(k).map(|&index| index.to_usize())
+    }
+
+    /// The position in the permutation of the first element for which `pred` is false,
+    /// assuming that the permutation is partitioned by `pred`,
+    /// for example if it is monotone and the permutation has been sorted by `sort_by`.
+    pub fn partition_point<P>(&self, mut pred: P) -> usize where P: FnMut(&T) -> bool {
+        let contents = &self.contents;
+        self.permutation.partition_point(|&index| pred(&contents[index.to_usize()]))
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.get()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.counters.reset();
+    }
+
+    /// The number of elements changed since the last sort, which may be out of order.
+    pub fn displaced_count(&self) -> usize {
+        self.dirty_count
+    }
+
+    /// The number of elements changed since the last sort.
+    #[deprecated(note = "use `displaced_count` instead")]
+    pub fn dirty_count(&self) -> usize {
+        self.displaced_count()
+    }
+
+    /// The number of ascending runs in the permutation.
+    /// This takes O(n) time, and does not change the permutation.
+    pub fn run_count_by<F>(&self, mut f: F) -> usize where F: FnMut(&T, &T) -> Ordering {
+        count_runs(&self.contents, &self.permutation, &mut f)
+    }
+
+    /// The number of pairs of elements which are out of order in the permutation.
+    /// This takes O(n log n) time, and does not change the permutation.
+    pub fn inversion_count_by<F>(&self, mut f: F) -> usize where F: FnMut(&T, &T) -> Ordering {
+        count_inversions(&self.contents, &self.permutation, &mut f)
+    }
+}
+
+impl<T, Ix> PermutedVec<T, Ix> where T: Ord, Ix: Index {
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> PermutedIndexedIter<'_, T, Ix> {
+        self.sorted_indexed_iter_by(T::cmp)
+    }
+
+    /// Binary search the permutation for a value.
+    /// Returns `Ok` of its position if the value is present,
+    /// and `Err` of the position it would be inserted at if not.
+    /// The permutation should be sorted, for example by `sort_by(T::cmp)`.
+    pub fn binary_search_sorted(&self, value: &T) -> Result<usize, usize> {
+        let permuted = self.lower_bound(value);
+        match self.get_permuted(permuted) {
+            Some(other) if other == value => Ok(permuted),
+            _ => Err(permuted),
+        }
+    }
+
+    /// The position in the permutation of the first element not less than `value`.
+    /// The permutation should be sorted, for example by `sort_by(T::cmp)`.
+    pub fn lower_bound(&self, value: &T) -> usize {
+        self.partition_point(|other| other < value)
+    }
+
+    /// The position in the permutation of the first element greater than `value`.
+    /// The permutation should be sorted, for example by `sort_by(T::cmp)`.
+    pub fn upper_bound(&self, value: &T) -> usize {
+        self.partition_point(|other| other <= value)
+    }
+
+    /// An iterator over the elements of the permutation in the given range of values.
+    /// The permutation should be sorted, for example by `sort_by(T::cmp)`.
+    pub fn sorted_range<R>(&self, range: R) -> PermutedIter<'_, T, Ix> where R: RangeBounds<T> {
+        let range = range_positions(&range, self.len(), |value| self.lower_bound(value), |value| self.upper_bound(value));
+        self.permuted_range_iter(range)
+    }
+}
+
+// The number of ascending runs in the elements of `contents`, in the order given by `order`.
+pub(crate) fn count_runs<T, Ix, F>(contents: &[T], order: &[Ix], f: &mut F) -> usize
+    where Ix: Index, F: FnMut(&T, &T) -> Ordering
+{
+    if order.is_empty() {
+        0
+    } else {
+        1 + order.windows(2).filter(|pair| f(&contents[pair[0].to_usize()], &contents[pair[1].to_usize()]) == Ordering::Greater).count()
+    }
+}
+
+// The number of pairs of elements of `contents` out of order, in the order given by `order`,
+// counted by merge sorting a copy of the order.
+pub(crate) fn count_inversions<T, Ix, F>(contents: &[T], order: &[Ix], f: &mut F) -> usize
+    where Ix: Index, F: FnMut(&T, &T) -> Ordering
+{
+    let mut order = order.to_vec();
+    let mut buffer = order.clone();
+    merge_count_inversions(contents, &mut order, &mut buffer, f)
+}
+
+fn merge_count_inversions<T, Ix, F>(contents: &[T], order: &mut [Ix], buffer: &mut [Ix], f: &mut F) -> usize
+    where Ix: Index, F: FnMut(&T, &T) -> Ordering
+{
+    let len = order.len();
+    if len < 2 {
+        return 0;
+    }
+    let mid = len / 2;
+    let mut count =
+        merge_count_inversions(contents, &mut order[..mid], &mut buffer[..mid], f) +
+        merge_count_inversions(contents, &mut order[mid..], &mut buffer[mid..], f);
+    let mut i = 0;
+    let mut j = mid;
+    for slot in buffer.iter_mut() {
+        if j == len || (i < mid && f(&contents[order[i].to_usize()], &contents[order[j].to_usize()]) != Ordering::Greater) {
+            *slot = order[i];
+            i += 1;
+        } else {
+            // Every element remaining in the first half is greater than this one
+            *slot = order[j];
+            j += 1;
+            count += mid - i;
+        }
+    }
+    order.copy_from_slice(buffer);
+    count
+}
+
+// The positions in a sorted sequence of length `len` of the values in `range`,
+// given functions computing the lower and upper bounds of a value.
+pub(crate) fn range_positions<T, R, L, U>(range: &R, len: usize, mut lower_bound: L, mut upper_bound: U) -> Range<usize>
+    where R: RangeBounds<T>, L: FnMut(&T) -> usize, U: FnMut(&T) -> usize
+{
+    let start = match range.start_bound() {
+        Bound::Included(value) => lower_bound(value),
+        Bound::Excluded(value) => upper_bound(value),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(value) => upper_bound(value),
+        Bound::Excluded(value) => lower_bound(value),
+        Bound::Unbounded => len,
+    };
+    start..end.max(start)
+}
+
+// Merge two sorted runs of a permutation onto the end of a buffer.
+fn merge_into<T, Ix, F>(contents: &[T], f: &mut F, run_1: &[Ix], run_2: &[Ix], buffer: &mut Vec<Ix>)
+    where Ix: Index, F: FnMut(&T, &T) -> Ordering
+{
+    let (mut permuted_1, mut permuted_2) = (0, 0);
+    while permuted_1 < run_1.len() && permuted_2 < run_2.len() {
+        if compare_indexes(contents, f, run_1[permuted_1], run_2[permuted_2]) == Ordering::Greater {
+            buffer.push(run_2[permuted_2]);
+            permuted_2 += 1;
+        } else {
+            buffer.push(run_1[permuted_1]);
+            permuted_1 += 1;
+        }
+    }
+    buffer.extend_from_slice(&run_1[permuted_1..]);
+    buffer.extend_from_slice(&run_2[permuted_2..]);
+}
+
+// Compare two elements of a vector, breaking ties by index.
+fn compare_indexes<T, Ix, F>(contents: &[T], f: &mut F, index_1: Ix, index_2: Ix) -> Ordering
+    where Ix: Index, F: FnMut(&T, &T) -> Ordering
+{
+    match f(&contents[index_1.to_usize()], &contents[index_2.to_usize()]) {
+        Ordering::Equal => index_1.cmp(&index_2),
+        ord => ord,
+    }
+}
+
+impl<T, Ix> PermutedVec<T, Ix> where Ix: Index {
+    // A permuted vector with the given contents and permutation,
+    // in which every element has changed since the last sort.
+    fn with_permutation(contents: Vec<T>, permutation: Vec<Ix>) -> PermutedVec<T, Ix> {
+        let len = contents.len();
+        let mut result = PermutedVec {
+            contents,
+            permutation,
+            spare_permutation: Vec::new(),
+            run_ends: Vec::new(),
+            spare_run_ends: Vec::new(),
+            repaired: Vec::new(),
+            inverse: Vec::with_capacity(len),
+            changes: vec![Change::Changed; len],
+            changed: (0..len).map(Ix::from_usize).collect(),
+            dirty_count: len,
+            sorted_by_other: false,
+            counters: Counters::default(),
+        };
+        result.reset_inverse();
+        result
     }
 }
 
 impl<T> From<Vec<T>> for PermutedVec<T> {
     fn from(vec: Vec<T>) -> PermutedVec<T> {
         let len = vec.len();
+        PermutedVec::with_permutation(vec, (0..len).collect())
+    }
+}
+
+impl<T, Ix> PartialEq for PermutedVec<T, Ix> where T: PartialEq, Ix: PartialEq {
+    fn eq(&self, other: &PermutedVec<T, Ix>) -> bool {
+        self.contents == other.contents && self.permutation == other.permutation
+    }
+}
+
+impl<T, Ix> Eq for PermutedVec<T, Ix> where T: Eq, Ix: Eq {}
+
+impl<T, Ix> Default for PermutedVec<T, Ix> where Ix: Index {
+    fn default() -> PermutedVec<T, Ix> {
         PermutedVec {
-            contents: vec,
-            permutation: (0..len).collect(),
+            contents: Vec::new(),
+            permutation: Vec::new(),
+            spare_permutation: Vec::new(),
+            run_ends: Vec::new(),
+            spare_run_ends: Vec::new(),
+            repaired: Vec::new(),
+            inverse: Vec::new(),
+            changes: Vec::new(),
+            changed: Vec::new(),
+            dirty_count: 0,
+            sorted_by_other: false,
+            counters: Counters::default(),
         }
     }
 }
 
 
 #[cfg(feature = "serde")]
-impl<T> serde::Serialize for PermutedVec<T>
-    where T: serde::Serialize
+impl<T, Ix> serde::Serialize for PermutedVec<T, Ix>
+    where T: serde::Serialize, Ix: serde::Serialize
 {
     fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
         where S: serde::Serializer
@@ -149,23 +968,26 @@ impl<T> serde::Serialize for PermutedVec<T>
 }
 
 #[cfg(feature = "serde")]
-impl<T> serde::Deserialize for PermutedVec<T>
-    where T: serde::Deserialize
+impl<T, Ix> serde::Deserialize for PermutedVec<T, Ix>
+    where T: serde::Deserialize, Ix: serde::Deserialize+Index
 {
-    fn deserialize<D>(deserializer: &mut D) -> Result<PermutedVec<T>, D::Error>
+    fn deserialize<D>(deserializer: &mut D) -> Result<PermutedVec<T, Ix>, D::Error>
         where D: serde::Deserializer
     {
-        let (contents, permutation) = try!(serde::Deserialize::deserialize(deserializer));
-        Ok(PermutedVec { contents: contents, permutation: permutation })
+        let (contents, permutation): (Vec<T>, Vec<Ix>) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(PermutedVec::with_permutation(contents, permutation))
     }
 }
 
 #[cfg(feature = "heapsize")]
-impl<T> heapsize::HeapSizeOf for PermutedVec<T>
-    where T: heapsize::HeapSizeOf
+impl<T, Ix> heapsize::HeapSizeOf for PermutedVec<T, Ix>
+    where T: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
 {
     fn heap_size_of_children(&self) -> usize {
-        self.contents.heap_size_of_children() + self.permutation.heap_size_of_children()
+        self.contents.heap_size_of_children() + self.permutation.heap_size_of_children() +
+        self.spare_permutation.heap_size_of_children() + self.run_ends.heap_size_of_children() +
+        self.spare_run_ends.heap_size_of_children() + self.repaired.heap_size_of_children() +
+        self.inverse.heap_size_of_children() + self.changes.heap_size_of_children() + self.changed.heap_size_of_children()
     }
 }
 
@@ -269,6 +1091,266 @@ fn test_set() {
     assert_eq!(vec.get(4), None);
     assert_eq!(vec.is_sorted_by(&mut usize::cmp), false);
     assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&0, &1, &10, &30]);
+
+    *vec.get_mut(2).unwrap() = 40;
+    assert_eq!(vec.get(2), Some(&40));
+    assert_eq!(vec.get_mut(4), None);
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), false);
+    assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&0, &10, &30, &40]);
+}
+
+#[test]
+fn test_remove_insert() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10, 40]);
+    assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&0, &10, &20, &30, &40]);
+
+    assert_eq!(vec.remove(1), 30);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get(1), Some(&20));
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), true);
+    assert_eq!(vec.permuted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &40]);
+
+    assert_eq!(vec.swap_remove(0), 0);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get(0), Some(&40));
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), true);
+    assert_eq!(vec.permuted_iter().collect::<Vec<&usize>>(), vec![&10, &20, &40]);
+
+    vec.insert(1, 15);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get(1), Some(&15));
+    assert_eq!(vec.get(2), Some(&20));
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), false);
+    assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&10, &15, &20, &40]);
+}
+
+#[test]
+fn test_truncate() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10, 40]);
+    assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&0, &10, &20, &30, &40]);
+
+    vec.truncate(3);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get(3), None);
+    assert_eq!(vec.permutation_iter().collect::<Vec<&usize>>(), vec![&0, &2, &1]);
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), true);
+    assert_eq!(vec.sorted_iter_by(usize::cmp).collect::<Vec<&usize>>(), vec![&0, &20, &30]);
+}
+
+#[test]
+fn test_rank() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10]);
+    vec.sort_by(usize::cmp);
+    assert_eq!(vec.rank_of(0), 0);
+    assert_eq!(vec.rank_of(1), 3);
+    assert_eq!(vec.rank_of(2), 2);
+    assert_eq!(vec.rank_of(3), 1);
+    assert_eq!(vec.nth_sorted(1), Some(&10));
+    assert_eq!(vec.nth_sorted(4), None);
+    assert_eq!(vec.index_of_nth_sorted(3), Some(1));
+    assert_eq!(vec.index_of_nth_sorted(4), None);
+}
+
+#[test]
+fn test_search() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10, 20]);
+    vec.sort_by(usize::cmp);
+    assert_eq!(vec.binary_search_sorted(&10), Ok(1));
+    assert_eq!(vec.binary_search_sorted(&15), Err(2));
+    assert_eq!(vec.binary_search_sorted(&40), Err(5));
+    assert_eq!(vec.lower_bound(&20), 2);
+    assert_eq!(vec.upper_bound(&20), 4);
+    assert_eq!(vec.sorted_range(&10..&30).collect::<Vec<&usize>>(), vec![&10, &20, &20]);
+    assert_eq!(vec.sorted_range(&10..=&30).collect::<Vec<&usize>>(), vec![&10, &20, &20, &30]);
+    assert_eq!(vec.sorted_range(&25..).collect::<Vec<&usize>>(), vec![&30]);
+    assert_eq!(vec.sorted_range(&30..&10).count(), 0);
+}
+
+#[test]
+fn test_iter() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10]);
+    let mut iter = vec.sorted_iter_by(usize::cmp);
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(&0));
+    assert_eq!(iter.next_back(), Some(&30));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.nth(1), Some(&20));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(vec.permuted_iter().rev().take(3).collect::<Vec<&usize>>(), vec![&30, &20, &10]);
+    assert_eq!(vec.permuted_iter().skip(2).collect::<Vec<&usize>>(), vec![&20, &30]);
+}
+
+#[test]
+fn test_indexed_iter() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.permuted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(0, &0), (1, &30), (2, &20), (3, &10)]);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(0, &0), (3, &10), (2, &20), (1, &30)]);
+    assert_eq!(vec.sorted_indexed_iter_by(|a, b| b.cmp(a)).next_back(), Some((0, &0)));
+}
+
+#[test]
+fn test_dirty() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut compare = |value_1: &usize, value_2: &usize| { calls.set(calls.get() + 1); value_1.cmp(value_2) };
+    let mut vec = PermutedVec::from((0..100).map(|value| value * 10).collect::<Vec<usize>>());
+    vec.resort_by(&mut compare);
+    assert_eq!(calls.get(), 99);
+
+    calls.set(0);
+    vec.resort_by(&mut compare);
+    assert_eq!(calls.get(), 0);
+
+    vec.set(10, 101);
+    vec.resort_by(&mut compare);
+    assert_eq!(calls.get(), 2);
+
+    calls.set(0);
+    vec.set(10, 555);
+    vec.set(90, 5);
+    vec.push(1);
+    vec.resort_by(&mut compare);
+    assert!(calls.get() < 40);
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), true);
+    assert_eq!(vec.index_of_nth_sorted(0), Some(0));
+    assert_eq!(vec.index_of_nth_sorted(1), Some(100));
+    assert_eq!(vec.index_of_nth_sorted(2), Some(90));
+    assert_eq!(vec.rank_of(10), 57);
+
+    vec.sort_by(|value_1, value_2| value_2.cmp(value_1));
+    assert_eq!(vec.nth_sorted(0), Some(&990));
+    assert_eq!(vec.nth_sorted(100), Some(&0));
+
+    vec.resort_by(&mut compare);
+    assert_eq!(vec.nth_sorted(0), Some(&0));
+    assert_eq!(vec.nth_sorted(100), Some(&990));
+}
+
+#[test]
+fn test_merge_runs() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut compare = |value_1: &usize, value_2: &usize| { calls.set(calls.get() + 1); value_1.cmp(value_2) };
+    let mut vec = PermutedVec::from((0..300).map(|value| (value % 100) * 3 + value / 100).collect::<Vec<usize>>());
+    vec.merge_runs_by(&mut compare);
+    assert_eq!(vec.is_sorted_by(&mut usize::cmp), true);
+    assert_eq!(vec.permuted_iter().cloned().collect::<Vec<usize>>(), (0..300).collect::<Vec<usize>>());
+    // One pass to find the three runs, and two passes to merge them
+    assert!(calls.get() < 300 + 200 + 300);
+}
+
+#[test]
+fn test_spare_permutation() {
+    let mut vec = PermutedVec::from((0..10).collect::<Vec<usize>>());
+    vec.resort_by(usize::cmp);
+    vec.set(0, 20);
+    vec.resort_by(usize::cmp);
+    let buffers = (vec.permutation.as_ptr(), vec.spare_permutation.as_ptr());
+    vec.set(0, 5);
+    vec.resort_by(usize::cmp);
+    assert_eq!((vec.spare_permutation.as_ptr(), vec.permutation.as_ptr()), buffers);
+    assert_eq!(vec.permuted_iter().take(7).collect::<Vec<&usize>>(), vec![&1, &2, &3, &4, &5, &5, &6]);
+
+    vec.shrink_to_fit();
+    assert_eq!(vec.spare_permutation.capacity(), 0);
+}
+
+#[test]
+fn test_scratch_buffers() {
+    let mut vec = PermutedVec::from((0..10).rev().collect::<Vec<usize>>());
+    vec.sort_with_by(SortStrategy::Merge, usize::cmp);
+    let mut run_ends = [vec.run_ends.as_ptr(), vec.spare_run_ends.as_ptr()];
+    vec.set(0, 20);
+    vec.set(9, 30);
+    vec.sort_with_by(SortStrategy::Merge, usize::cmp);
+    // Each pass of merging swaps the two buffers of run ends
+    let mut reused = [vec.run_ends.as_ptr(), vec.spare_run_ends.as_ptr()];
+    run_ends.sort();
+    reused.sort();
+    assert_eq!(reused, run_ends);
+
+    vec.set(5, 0);
+    vec.sort_with_by(SortStrategy::Repair, usize::cmp);
+    let repaired = vec.repaired.as_ptr();
+    vec.set(6, 11);
+    vec.sort_with_by(SortStrategy::Repair, usize::cmp);
+    assert_eq!(vec.repaired.as_ptr(), repaired);
+    assert_eq!(vec.permuted_iter().collect::<Vec<&usize>>(), vec![&0, &1, &2, &5, &6, &7, &8, &11, &20, &30]);
+
+    vec.shrink_to_fit();
+    assert_eq!(vec.run_ends.capacity(), 0);
+    assert_eq!(vec.repaired.capacity(), 0);
+}
+
+#[test]
+fn test_metrics() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10, 40]);
+    assert_eq!(vec.displaced_count(), 5);
+    #[allow(deprecated)]
+    let dirty_count = vec.dirty_count();
+    assert_eq!(dirty_count, 5);
+    assert_eq!(vec.run_count_by(usize::cmp), 3);
+    assert_eq!(vec.inversion_count_by(usize::cmp), 3);
+    assert_eq!(vec.permuted_iter().collect::<Vec<&usize>>(), vec![&0, &30, &20, &10, &40]);
+    vec.sort_by(usize::cmp);
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.run_count_by(usize::cmp), 1);
+    assert_eq!(vec.inversion_count_by(usize::cmp), 0);
+    vec.set(0, 50);
+    assert_eq!(vec.displaced_count(), 1);
+    assert_eq!(vec.run_count_by(usize::cmp), 2);
+    assert_eq!(vec.inversion_count_by(usize::cmp), 4);
+    assert_eq!(PermutedVec::<usize>::new().run_count_by(usize::cmp), 0);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats() {
+    let mut vec = PermutedVec::from(vec![0, 30, 20, 10]);
+    vec.resort_by(usize::cmp);
+    assert_eq!(vec.stats().full_sorts, 1);
+    vec.resort_by(usize::cmp);
+    assert_eq!(vec.stats().skipped_sorts, 1);
+    vec.reset_stats();
+    vec.set(1, 35);
+    vec.resort_by(usize::cmp);
+    assert_eq!(vec.stats(), Stats { comparisons: 1, skipped_sorts: 1, unsorted: 0, full_sorts: 0 });
+    vec.set(1, 15);
+    vec.resort_by(usize::cmp);
+    assert_eq!(vec.stats().skipped_sorts, 1);
+    assert_eq!(vec.stats().full_sorts, 0);
+}
+
+#[test]
+fn test_index_type() {
+    let mut vec: PermutedVec<usize, u16> = PermutedVec::default();
+    for value in &[30, 10, 20, 0] {
+        vec.push(*value);
+    }
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(3, &0), (1, &10), (2, &20), (0, &30)]);
+    assert_eq!(vec.permutation_iter().collect::<Vec<&u16>>(), vec![&3, &1, &2, &0]);
+    vec.remove(1);
+    assert_eq!(vec.rank_of(0), 2);
+    assert_eq!(vec.index_of_nth_sorted(1), Some(1));
+}
+
+#[cfg(feature = "heapsize")]
+#[test]
+fn test_index_heapsize() {
+    use heapsize::HeapSizeOf;
+
+    // Sort both vectors, so the spare permutations are allocated
+    let mut wide = PermutedVec::from(vec![0u8; 1000]);
+    wide.set(0, 1);
+    wide.resort_by(u8::cmp);
+    let mut narrow: PermutedVec<u8, u16> = PermutedVec::new_in();
+    narrow.extend_edits(vec![0u8; 1000]);
+    narrow.set(0, 1);
+    narrow.resort_by(u8::cmp);
+    assert!(narrow.heap_size_of_children() < wide.heap_size_of_children());
 }
 
 #[cfg(feature = "serde_json")]
@@ -1,91 +1,333 @@
-use permuted_vec::{PermutedIter, PermutedVec};
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+use std::ops::{Deref, DerefMut, RangeBounds};
+use std::slice::Iter;
+
+use compare::{ByKey, Compare, Natural};
+use index::Index;
+#[cfg(feature = "stats")]
+use stats::Stats;
+use stats::Counters;
+use permuted_vec::{PermutedIndexedIter, PermutedIter, PermutedVec, SortStrategy, range_positions};
 
 /// The type of presorted vectors.
+///
+/// The vector is ordered by a comparator `C`, which by default is
+/// the natural order on `T`.
+///
+/// Elements which are pushed or set out of order are remembered as displaced,
+/// and sorting takes them out of the permutation, sorts them, and merges them back,
+/// without comparing the elements which are still in order.
+///
+/// The permutation and its inverse are stored using the index type `Ix`,
+/// as in a `PermutedVec`.
 #[derive(Clone,Debug,Eq,PartialEq)]
-pub struct PresortedVec<T> where T: Ord {
+pub struct PresortedVec<T, C = Natural, Ix = usize> {
     // The contents of the vector.
-    contents: PermutedVec<T>,
-    // The inverse permutation
-    inverse: Vec<usize>,
-    // Is the permiuted vector sorted?
-    is_sorted: bool,
+    contents: PermutedVec<T, Ix>,
+    // The comparator used to sort the vector
+    compare: C,
+    // Counters of the work done, other than by the permuted vector
+    counters: Counters,
 }
 
 /// The type of presorted iterators over a presorted vector.
 #[derive(Clone,Debug)]
-pub struct PresortedIter<'a, T> where T: Ord+'a {
+pub struct PresortedIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
     // The underlying iterator
-    contents: PermutedIter<'a, T>,
+    contents: PermutedIter<'a, T, Ix>,
 }
 
-impl<'a, T> Iterator for PresortedIter<'a, T> where T: 'a+Ord {
+impl<'a, T, Ix> Iterator for PresortedIter<'a, T, Ix> where T: 'a, Ix: Index {
     type Item = &'a T;
     fn next(&mut self) -> Option<&'a T> {
         self.contents.next()
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.contents.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.contents.nth(n)
+    }
+}
+
+impl<'a, T, Ix> DoubleEndedIterator for PresortedIter<'a, T, Ix> where T: 'a, Ix: Index {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.contents.next_back()
+    }
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        self.contents.nth_back(n)
+    }
+}
+
+impl<'a, T, Ix> ExactSizeIterator for PresortedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+impl<'a, T, Ix> FusedIterator for PresortedIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+/// A mutable reference to an element of a presorted vector,
+/// which checks whether the vector is still sorted when it is dropped.
+pub struct PresortedRefMut<'a, T, C, Ix = usize> where T: 'a, C: 'a + Compare<T>, Ix: 'a + Index {
+    // The vector being mutated
+    vec: &'a mut PresortedVec<T, C, Ix>,
+    // The index of the element being mutated
+    index: usize,
+}
+
+impl<'a, T, C, Ix> Deref for PresortedRefMut<'a, T, C, Ix> where T: 'a, C: 'a + Compare<T>, Ix: 'a + Index {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // The index was checked when the reference was created
+        self.vec.contents.get(self.index).unwrap()
+    }
+}
+
+impl<'a, T, C, Ix> DerefMut for PresortedRefMut<'a, T, C, Ix> where T: 'a, C: 'a + Compare<T>, Ix: 'a + Index {
+    fn deref_mut(&mut self) -> &mut T {
+        self.vec.contents.get_mut(self.index).unwrap()
+    }
 }
 
-impl<T> PresortedVec<T> where T:Ord {
+impl<'a, T, C, Ix> Drop for PresortedRefMut<'a, T, C, Ix> where T: 'a, C: 'a + Compare<T>, Ix: 'a + Index {
+    fn drop(&mut self) {
+        self.vec.check_sorted_at(self.index);
+    }
+}
+
+impl<T> PresortedVec<T> where T: Ord {
+    /// Create a new, empty presorted vector.
+    pub fn new() -> PresortedVec<T> {
+        PresortedVec::new_in()
+    }
+
+    /// Create a new, empty presorted vector, with space for `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> PresortedVec<T> {
+        PresortedVec::with_capacity_in(capacity)
+    }
+}
+
+impl<T, K, F> PresortedVec<T, ByKey<F>> where F: Fn(&T) -> K, K: Ord {
+    /// Create a new, empty presorted vector, sorted by a key extracted from each element.
+    pub fn new_by_key(f: F) -> PresortedVec<T, ByKey<F>> {
+        PresortedVec::new_by_key_in(f)
+    }
+}
+
+impl<T, C> PresortedVec<T, C> where C: Compare<T> {
+    /// Create a new, empty presorted vector, sorted by the given comparator.
+    pub fn new_by(compare: C) -> PresortedVec<T, C> {
+        PresortedVec::new_by_in(compare)
+    }
+}
+
+impl<T, Ix> PresortedVec<T, Natural, Ix> where T: Ord, Ix: Index {
+    /// Create a new, empty presorted vector,
+    /// for any index type, for example `PresortedVec::<T, Natural, u32>::new_in()`.
+    pub fn new_in() -> PresortedVec<T, Natural, Ix> {
+        PresortedVec::new_by_in(Natural)
+    }
+
+    /// Create a new, empty presorted vector, with space for `capacity` elements,
+    /// for any index type.
+    pub fn with_capacity_in(capacity: usize) -> PresortedVec<T, Natural, Ix> {
+        let mut result = PresortedVec::new_in();
+        result.reserve(capacity);
+        result
+    }
+}
+
+impl<T, K, F, Ix> PresortedVec<T, ByKey<F>, Ix> where F: Fn(&T) -> K, K: Ord, Ix: Index {
+    /// Create a new, empty presorted vector, sorted by a key extracted from each element,
+    /// for any index type.
+    pub fn new_by_key_in(f: F) -> PresortedVec<T, ByKey<F>, Ix> {
+        PresortedVec::new_by_in(ByKey(f))
+    }
+}
+
+impl<T, C, Ix> PresortedVec<T, C, Ix> where C: Compare<T>, Ix: Index {
+    /// Create a new, empty presorted vector, sorted by the given comparator,
+    /// for any index type, for example `PresortedVec::<T, C, u32>::new_by_in(compare)`.
+    pub fn new_by_in(compare: C) -> PresortedVec<T, C, Ix> {
+        PresortedVec {
+            contents: PermutedVec::new_in(),
+            compare,
+            counters: Counters::default(),
+        }
+    }
+
+    /// Reserve space for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        self.contents.reserve(additional);
+    }
+
+    /// Shrink the space used by the vector as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.contents.shrink_to_fit();
+    }
+
     /// The length of the vector.
     pub fn len(&self) -> usize {
         self.contents.len()
     }
 
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
     /// Append an element to the end of the vector.    
     pub fn push(&mut self, value: T) {
         //println!("vec push index {:?}", self.len());
         let permuted = self.contents.len();
-        self.is_sorted =
-            self.is_sorted &&
-            self.contents.get_permuted(permuted.wrapping_sub(1)).map(|before| before <= &value).unwrap_or(true);
+        let is_sorted = self.in_order_at(permuted.wrapping_sub(1), |before| self.le(before, &value));
         self.contents.push(value);
-        self.inverse.push(permuted);
+        if is_sorted {
+            self.contents.mark_clean(permuted);
+        }
     }
 
     /// Set the `i`th element of the vector.
     /// Panics if the vector contains fewer than `i` elements.
     pub fn set(&mut self, index: usize, value: T) {
-        let permuted = self.inverse[index];
-        self.is_sorted =
-            self.is_sorted &&
-            self.contents.get_permuted(permuted.wrapping_sub(1)).map(|before| before <= &value).unwrap_or(true) &&
-            self.contents.get_permuted(permuted.wrapping_add(1)).map(|after| &value <= after).unwrap_or(true);
         self.contents.set(index, value);
+        self.check_sorted_at(index);
+    }
+
+    /// Set many elements of the vector at once.
+    /// Unlike `set`, the edited elements are not compared with their neighbours
+    /// until the next sort, so each element is checked once however often it is edited.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        for (index, value) in edits {
+            self.contents.set(index, value);
+        }
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// Unlike `push`, the appended elements are not compared with their neighbours
+    /// until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        for value in values {
+            self.contents.push(value);
+        }
+    }
+
+    // Check whether the `i`th element is in order, and if not remember it as displaced.
+    fn check_sorted_at(&mut self, index: usize) {
+        if self.is_sorted_at(index) {
+            self.contents.mark_clean(index);
+        } else {
+            self.contents.mark_dirty(index);
+        }
+    }
+
+    // Is the `i`th element in order with its neighbours in the permutation?
+    fn is_sorted_at(&self, index: usize) -> bool {
+        let permuted = self.contents.rank_of(index);
+        self.contents.get(index).map(|value|
+            self.in_order_at(permuted.wrapping_sub(1), |before| self.le(before, value)) &&
+            self.in_order_at(permuted.wrapping_add(1), |after| self.le(value, after))
+        ).unwrap_or(true)
+    }
+
+    // Is the element at a position in the permutation in order, as checked by `ordered`?
+    // Displaced elements are never in order, since they may yet move,
+    // and positions outside the permutation always are.
+    fn in_order_at<F>(&self, permuted: usize, ordered: F) -> bool where F: FnOnce(&T) -> bool {
+        match self.contents.index_of_nth_sorted(permuted) {
+            Some(index) => !self.contents.is_dirty(index) && self.contents.get(index).map(ordered).unwrap_or(true),
+            None => true,
+        }
     }
 
-    /// Truncate this vector and reset the sort if necessary.
+    /// Truncate this vector.
+    /// This does not change whether the vector is sorted.
     pub fn truncate(&mut self, len: usize) {
         //println!("vec truncate to {:?}", len);
         if len < self.len() {
             self.contents.truncate(len);
-            self.inverse.clear();
-            self.inverse.extend(0..len);
-            self.is_sorted = false;
+        }
+    }
+
+    /// Remove and return the `i`th element of the vector,
+    /// shifting all elements after it down by one.
+    /// This does not change whether the vector is sorted.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn remove(&mut self, index: usize) -> T {
+        self.contents.remove(index)
+    }
+
+    /// Remove and return the `i`th element of the vector,
+    /// replacing it by the last element of the vector.
+    /// This does not change whether the vector is sorted.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.contents.swap_remove(index)
+    }
+
+    /// Insert an element as the `i`th element of the vector,
+    /// shifting all elements after it up by one.
+    /// If the vector is sorted, the element is placed in sort order,
+    /// so the vector remains sorted.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let is_sorted = self.contents.is_clean();
+        let permuted = if is_sorted {
+            // Binary search for the first element greater than the value
+            let mut lo = 0;
+            let mut hi = self.len();
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if self.contents.get_permuted(mid).map(|before| self.le(before, &value)).unwrap_or(false) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            lo
+        } else {
+            self.len()
+        };
+        self.contents.insert_permuted(index, permuted, value);
+        if is_sorted {
+            self.contents.mark_clean(index);
         }
     }
 
     /// Sort the permutation on the vector
     pub fn sort(&mut self) {
-        if !self.is_sorted {
-            self.contents.sort_by(|value_1, value_2| value_1.cmp(value_2));
-            for (i, &j) in self.contents.permutation_iter().enumerate() {
-                self.inverse[j] = i;
-            }
-            self.is_sorted = true;
+        if self.contents.is_clean() {
+            self.counters.skipped_sort();
+        } else {
+            let compare = &self.compare;
+            self.contents.resort_by(|value_1, value_2| compare.compare(value_1, value_2));
         }
     }
 
-    /// Create a new, empty presorted vector.
-    pub fn new() -> PresortedVec<T> {
-        PresortedVec {
-            contents: PermutedVec::new(),
-            inverse: Vec::new(),
-            is_sorted: true,
+    // Mark the `i`th element as displaced, so it is repaired at the next sort.
+    pub(crate) fn mark_displaced(&mut self, index: usize) {
+        self.contents.mark_dirty(index);
+    }
+
+    // Sort the permutation on the vector using the given strategy.
+    pub(crate) fn sort_with(&mut self, strategy: SortStrategy) {
+        if !self.contents.is_clean() {
+            let compare = &self.compare;
+            self.contents.sort_with_by(strategy, |value_1, value_2| compare.compare(value_1, value_2));
+        }
+    }
+
+    // Sort the permutation on the vector by a radix sort of integer keys of `bytes` bytes,
+    // which must be ordered in the same way as the comparator.
+    pub(crate) fn radix_sort_by_key<F>(&mut self, bytes: usize, key: F) where F: Fn(&T) -> u64 {
+        if self.contents.is_clean() {
+            self.counters.skipped_sort();
+        } else {
+            self.contents.radix_sort_by_key(bytes, key);
         }
     }
 
     /// An iterator over the presorted vector
-    pub fn presorted_iter(&self) -> PresortedIter<T> {
+    pub fn presorted_iter(&self) -> PresortedIter<'_, T, Ix> {
         PresortedIter {
             contents: self.contents.permuted_iter(),
         }
@@ -93,69 +335,206 @@ impl<T> PresortedVec<T> where T:Ord {
 
     /// Is the presorted vector already sorted?
     pub fn is_sorted(&self) -> bool {
-        self.is_sorted || self.contents.is_sorted_by(&mut |value_1, value_2| value_1.cmp(value_2))
+        self.contents.dirty_is_sorted_by(&mut |value_1, value_2| self.compare.compare(value_1, value_2))
     }
 
     /// A sorted iterator over the vector.
-    pub fn sorted_iter(&mut self) -> PresortedIter<T> {
+    pub fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
         self.sort();
         self.presorted_iter()
     }
 
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> PermutedIndexedIter<'_, T, Ix> {
+        self.sort();
+        self.contents.permuted_indexed_iter()
+    }
+
     /// Get the `i`th element of the vector.
     /// Returns `None` if the vector contains fewer than `i` elements.
     pub fn get(&self, index: usize) -> Option<&T> {
         self.contents.get(index)
     }
 
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// Whether the vector is still sorted is checked when the reference is dropped.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_mut(&mut self, index: usize) -> Option<PresortedRefMut<'_, T, C, Ix>> {
+        if index < self.len() {
+            // If the reference is leaked, the element is left displaced
+            self.contents.mark_dirty(index);
+            Some(PresortedRefMut { vec: self, index })
+        } else {
+            None
+        }
+    }
+
     /// Get the `i`th element of the permuted vector.
     /// Returns `None` if the vector contains fewer than `i` elements.
     pub fn get_permuted(&self, permuted: usize) -> Option<&T> {
         self.contents.get_permuted(permuted)
     }
+
+    /// An iterator over the permutation
+    pub fn permutation_iter(&self) -> Iter<'_, Ix> {
+        self.contents.permutation_iter()
+    }
+
+    /// The rank of the `i`th element in sorted order.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn rank_of(&mut self, index: usize) -> usize {
+        self.sort();
+        self.contents.rank_of(index)
+    }
+
+    /// The `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn nth_sorted(&mut self, k: usize) -> Option<&T> {
+        self.sort();
+        self.contents.nth_sorted(k)
+    }
+
+    /// The index of the `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn index_of_nth_sorted(&mut self, k: usize) -> Option<usize> {
+        self.sort();
+        self.contents.index_of_nth_sorted(k)
+    }
+
+    /// Binary search the sorted vector for a value.
+    /// Returns `Ok` of its rank if the value is present,
+    /// and `Err` of the rank it would be inserted at if not.
+    pub fn binary_search_sorted(&mut self, value: &T) -> Result<usize, usize> {
+        let permuted = self.lower_bound(value);
+        match self.contents.get_permuted(permuted) {
+            Some(other) if self.compare.compare(other, value) == Ordering::Equal => Ok(permuted),
+            _ => Err(permuted),
+        }
+    }
+
+    /// The rank of the first element not less than `value`.
+    pub fn lower_bound(&mut self, value: &T) -> usize {
+        self.sort();
+        self.permuted_lower_bound(value)
+    }
+
+    /// The rank of the first element greater than `value`.
+    pub fn upper_bound(&mut self, value: &T) -> usize {
+        self.sort();
+        self.permuted_upper_bound(value)
+    }
+
+    /// A sorted iterator over the elements of the vector in the given range of values.
+    pub fn sorted_range<R>(&mut self, range: R) -> PresortedIter<'_, T, Ix> where R: RangeBounds<T> {
+        self.sort();
+        let range = range_positions(&range, self.len(),
+            |value| self.permuted_lower_bound(value),
+            |value| self.permuted_upper_bound(value));
+        PresortedIter {
+            contents: self.contents.permuted_range_iter(range),
+        }
+    }
+
+    // The position in the permutation of the first element not less than `value`.
+    fn permuted_lower_bound(&self, value: &T) -> usize {
+        self.contents.partition_point(|other| self.compare.compare(other, value) == Ordering::Less)
+    }
+
+    // The position in the permutation of the first element greater than `value`.
+    fn permuted_upper_bound(&self, value: &T) -> usize {
+        self.contents.partition_point(|other| self.le(other, value))
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.get() + self.contents.stats()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.counters.reset();
+        self.contents.reset_stats();
+    }
+
+    /// The number of elements displaced since the last sort, which may be out of order.
+    pub fn displaced_count(&self) -> usize {
+        self.contents.displaced_count()
+    }
+
+    /// The number of ascending runs in the permutation.
+    /// This takes O(n) time, and does not change the permutation.
+    pub fn run_count(&self) -> usize {
+        self.contents.run_count_by(|value_1, value_2| self.compare.compare(value_1, value_2))
+    }
+
+    /// The number of pairs of elements which are out of order in the permutation.
+    /// This takes O(n log n) time, and does not change the permutation.
+    pub fn inversion_count(&self) -> usize {
+        self.contents.inversion_count_by(|value_1, value_2| self.compare.compare(value_1, value_2))
+    }
+
+    /// The comparator used to sort the vector.
+    pub fn comparator(&self) -> &C {
+        &self.compare
+    }
+
+    // Is `value_1` no greater than `value_2` in the comparator's order?
+    fn le(&self, value_1: &T, value_2: &T) -> bool {
+        self.counters.compared(1);
+        self.compare.compare(value_1, value_2) != Ordering::Greater
+    }
 }
 
 impl<T> From<Vec<T>> for PresortedVec<T> where T: Ord {
     fn from(vec: Vec<T>) -> PresortedVec<T> {
-        let len = vec.len();
         PresortedVec {
             contents: PermutedVec::from(vec),
-            inverse: (0..len).collect(),
-            is_sorted: false,
+            compare: Natural,
+            counters: Counters::default(),
         }
     }
 }
 
+impl<T, C, Ix> Default for PresortedVec<T, C, Ix> where C: Compare<T> + Default, Ix: Index {
+    fn default() -> PresortedVec<T, C, Ix> {
+        PresortedVec::new_by_in(C::default())
+    }
+}
+
 
 #[cfg(feature = "serde")]
-impl<T> serde::Serialize for PresortedVec<T>
-    where T: serde::Serialize+Ord
+impl<T, Ix> serde::Serialize for PresortedVec<T, Natural, Ix>
+    where T: serde::Serialize+Ord, Ix: serde::Serialize+Index
 {
     fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
         where S: serde::Serializer
     {
-        (&self.contents, &self.inverse, &self.is_sorted).serialize(serializer)
+        (&self.contents, self.contents.inverse(), &self.is_sorted()).serialize(serializer)
     }
 }
 
 #[cfg(feature = "serde")]
-impl<T> serde::Deserialize for PresortedVec<T>
-    where T: serde::Deserialize+Ord
+impl<T, Ix> serde::Deserialize for PresortedVec<T, Natural, Ix>
+    where T: serde::Deserialize+Ord, Ix: serde::Deserialize+Index
 {
-    fn deserialize<D>(deserializer: &mut D) -> Result<PresortedVec<T>, D::Error>
+    fn deserialize<D>(deserializer: &mut D) -> Result<PresortedVec<T, Natural, Ix>, D::Error>
         where D: serde::Deserializer
     {
-        let (contents, inverse, is_sorted) = try!(serde::Deserialize::deserialize(deserializer));
-        Ok(PresortedVec { contents: contents, inverse: inverse, is_sorted: is_sorted })
+        // The inverse permutation is recomputed from the permutation
+        let (mut contents, _, is_sorted): (PermutedVec<T, Ix>, Vec<Ix>, bool) = serde::Deserialize::deserialize(deserializer)?;
+        if is_sorted { contents.mark_sorted(); }
+        Ok(PresortedVec { contents, compare: Natural, counters: Counters::default() })
     }
 }
 
 #[cfg(feature = "heapsize")]
-impl<T> heapsize::HeapSizeOf for PresortedVec<T>
-    where T: heapsize::HeapSizeOf
+impl<T, C, Ix> heapsize::HeapSizeOf for PresortedVec<T, C, Ix>
+    where T: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
 {
     fn heap_size_of_children(&self) -> usize {
-        self.contents.heap_size_of_children() + self.inverse.heap_size_of_children()
+        self.contents.heap_size_of_children()
     }
 }
 
@@ -261,6 +640,258 @@ fn test_set() {
     assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &1, &10, &30]);
 }
 
+#[test]
+fn test_sort_by() {
+    let mut vec = PresortedVec::new_by(|value_1: &usize, value_2: &usize| value_2.cmp(value_1));
+    vec.push(30);
+    vec.push(20);
+    assert_eq!(vec.is_sorted(), true);
+    vec.push(25);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&30, &25, &20]);
+
+    vec.set(2, 21);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&30, &21, &20]);
+
+    vec.set(2, 31);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&31, &30, &20]);
+}
+
+#[test]
+fn test_sort_by_key() {
+    let mut vec = PresortedVec::new_by_key(|&(z_index, _): &(usize, &str)| z_index);
+    vec.push((1, "b"));
+    vec.push((1, "a"));
+    vec.push((2, "c"));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&(usize, &str)>>(), vec![&(1, "b"), &(1, "a"), &(2, "c")]);
+
+    vec.set(1, (2, "a"));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&(usize, &str)>>(), vec![&(1, "b"), &(2, "a"), &(2, "c")]);
+
+    vec.set(0, (3, "b"));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&(usize, &str)>>(), vec![&(2, "a"), &(2, "c"), &(3, "b")]);
+}
+
+#[test]
+fn test_remove_insert() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10, 40]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &30, &40]);
+
+    assert_eq!(vec.remove(1), 30);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get(1), Some(&20));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &40]);
+
+    assert_eq!(vec.swap_remove(0), 0);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get(0), Some(&40));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&10, &20, &40]);
+
+    vec.insert(1, 15);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get(1), Some(&15));
+    assert_eq!(vec.get(2), Some(&20));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.presorted_iter().collect::<Vec<&usize>>(), vec![&10, &15, &20, &40]);
+
+    vec.set(1, 12);
+    assert_eq!(vec.is_sorted(), true);
+    vec.set(0, 5);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&5, &10, &12, &20]);
+}
+
+#[test]
+fn test_truncate() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10, 40]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &30, &40]);
+
+    vec.truncate(3);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get(3), None);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.presorted_iter().collect::<Vec<&usize>>(), vec![&0, &20, &30]);
+
+    vec.set(1, 25);
+    assert_eq!(vec.is_sorted(), true);
+    vec.set(2, 50);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &25, &50]);
+}
+
+#[test]
+fn test_rank() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.rank_of(0), 0);
+    assert_eq!(vec.rank_of(1), 3);
+    assert_eq!(vec.rank_of(2), 2);
+    assert_eq!(vec.rank_of(3), 1);
+    assert_eq!(vec.nth_sorted(1), Some(&10));
+    assert_eq!(vec.nth_sorted(4), None);
+    assert_eq!(vec.index_of_nth_sorted(3), Some(1));
+    assert_eq!(vec.index_of_nth_sorted(4), None);
+
+    vec.set(3, 40);
+    assert_eq!(vec.rank_of(3), 3);
+    assert_eq!(vec.rank_of(1), 2);
+    assert_eq!(vec.nth_sorted(1), Some(&20));
+}
+
+#[test]
+fn test_search() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10, 20]);
+    assert_eq!(vec.binary_search_sorted(&10), Ok(1));
+    assert_eq!(vec.binary_search_sorted(&15), Err(2));
+    assert_eq!(vec.binary_search_sorted(&40), Err(5));
+    assert_eq!(vec.lower_bound(&20), 2);
+    assert_eq!(vec.upper_bound(&20), 4);
+    assert_eq!(vec.sorted_range(&10..&30).collect::<Vec<&usize>>(), vec![&10, &20, &20]);
+    assert_eq!(vec.sorted_range(&10..=&30).collect::<Vec<&usize>>(), vec![&10, &20, &20, &30]);
+    assert_eq!(vec.sorted_range(&25..).collect::<Vec<&usize>>(), vec![&30]);
+    assert_eq!(vec.sorted_range(&30..&10).count(), 0);
+
+    vec.set(0, 25);
+    assert_eq!(vec.sorted_range(&20..&30).collect::<Vec<&usize>>(), vec![&20, &20, &25]);
+}
+
+#[test]
+fn test_iter() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10]);
+    let mut iter = vec.sorted_iter();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(&0));
+    assert_eq!(iter.next_back(), Some(&30));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.nth(1), Some(&20));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+    assert_eq!(vec.sorted_iter().rev().take(3).collect::<Vec<&usize>>(), vec![&30, &20, &10]);
+    assert_eq!(vec.sorted_iter().skip(2).collect::<Vec<&usize>>(), vec![&20, &30]);
+}
+
+#[test]
+fn test_indexed_iter() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(0, &0), (3, &10), (2, &20), (1, &30)]);
+    vec.set(0, 25);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &usize)>>(), vec![(3, &10), (2, &20), (0, &25), (1, &30)]);
+}
+
+#[test]
+fn test_get_mut() {
+    let mut vec = PresortedVec::from(vec![0, 30, 20, 10]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &30]);
+
+    *vec.get_mut(2).unwrap() += 1;
+    assert_eq!(vec.get(2), Some(&21));
+    assert_eq!(vec.is_sorted(), true);
+
+    *vec.get_mut(2).unwrap() += 10;
+    assert_eq!(vec.get(2), Some(&31));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &30, &31]);
+
+    assert!(vec.get_mut(4).is_none());
+    ::std::mem::forget(vec.get_mut(0));
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &30, &31]);
+}
+
+#[test]
+fn test_apply_edits() {
+    let mut vec = PresortedVec::from(vec![0, 10, 20, 30]);
+    vec.sort();
+    vec.apply_edits(vec![(1, 11), (2, 21), (1, 12)]);
+    assert_eq!(vec.get(1), Some(&12));
+    assert_eq!(vec.is_sorted(), true);
+    vec.extend_edits(vec![40, 50]);
+    assert_eq!(vec.len(), 6);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &12, &21, &30, &40, &50]);
+
+    vec.apply_edits(vec![(1, 35), (4, 45)]);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &21, &30, &35, &45, &50]);
+    assert_eq!(vec.is_sorted(), true);
+
+    vec.extend_edits(vec![5]);
+    vec.truncate(6);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &21, &30, &35, &45, &50]);
+}
+
+#[test]
+fn test_repair() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut vec = PresortedVec::new_by(|value_1: &usize, value_2: &usize| { calls.set(calls.get() + 1); value_1.cmp(value_2) });
+    for value in 0..100 {
+        vec.push(value * 10);
+    }
+    assert_eq!(calls.get(), 99);
+    assert_eq!(vec.is_sorted(), true);
+
+    calls.set(0);
+    vec.set(10, 105);
+    vec.set(20, 5);
+    vec.set(90, 15);
+    assert_eq!(calls.get(), 4);
+    vec.sort();
+    assert!(calls.get() < 40);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().take(5).collect::<Vec<&usize>>(), vec![&0, &5, &10, &15, &20]);
+    assert_eq!(vec.rank_of(10), 12);
+
+    calls.set(0);
+    vec.sort();
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn test_metrics() {
+    let mut vec = PresortedVec::new();
+    for value in [0, 10, 20, 30, 40] {
+        vec.push(value);
+    }
+    assert_eq!(vec.displaced_count(), 0);
+    vec.set(1, 35);
+    vec.set(3, 5);
+    assert_eq!(vec.displaced_count(), 2);
+    assert_eq!(vec.run_count(), 3);
+    assert_eq!(vec.inversion_count(), 3);
+    assert_eq!(vec.presorted_iter().collect::<Vec<&usize>>(), vec![&0, &35, &20, &5, &40]);
+    vec.sort();
+    assert_eq!(vec.displaced_count(), 0);
+    assert_eq!(vec.run_count(), 1);
+    assert_eq!(vec.inversion_count(), 0);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats() {
+    let mut vec = PresortedVec::new();
+    for value in 0..10 {
+        vec.push(value);
+    }
+    vec.sort();
+    assert_eq!(vec.stats(), Stats { comparisons: 9, skipped_sorts: 1, unsorted: 0, full_sorts: 0 });
+    vec.reset_stats();
+    vec.set(5, 0);
+    vec.sort();
+    vec.sort();
+    let stats = vec.stats();
+    assert_eq!(stats.skipped_sorts, 1);
+    assert_eq!(stats.full_sorts, 0);
+    assert!(stats.comparisons < 10);
+}
+
 #[cfg(feature = "serde_json")]
 #[test]
 fn test_serialize() {
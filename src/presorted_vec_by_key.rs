@@ -0,0 +1,357 @@
+use std::iter::FusedIterator;
+use std::ops::{Deref, DerefMut};
+use std::slice::Iter;
+
+use compare::Natural;
+use index::Index;
+use permuted_vec::PermutedIndexedIter;
+use presorted_vec::PresortedVec;
+#[cfg(feature = "stats")]
+use stats::Stats;
+
+/// The type of presorted vectors sorted by a cached key.
+///
+/// The key of each element is computed when the element is pushed or set,
+/// and stored alongside it, so sorting never recomputes keys.
+#[derive(Clone,Debug)]
+pub struct PresortedVecByKey<T, K, F = fn(&T) -> K, Ix = usize> {
+    // The contents of the vector.
+    contents: Vec<T>,
+    // The cached keys, which carry the permutation
+    keys: PresortedVec<K, Natural, Ix>,
+    // The indexes of elements whose cached key may be out of date,
+    // because a mutable reference to them was leaked
+    stale: Vec<usize>,
+    // The function used to compute keys
+    key: F,
+}
+
+/// The type of presorted iterators over a presorted vector sorted by key.
+#[derive(Clone,Debug)]
+pub struct PresortedByKeyIter<'a, T, Ix = usize> where T: 'a, Ix: 'a {
+    // The permutation
+    permutation: Iter<'a, Ix>,
+    // The contents of the iterator
+    contents: &'a[T],
+}
+
+impl<'a, T, Ix> Iterator for PresortedByKeyIter<'a, T, Ix> where T: 'a, Ix: Index {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.next().map(|&index| &contents[index.to_usize()])
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.permutation.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth(n).map(|&index| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, Ix> DoubleEndedIterator for PresortedByKeyIter<'a, T, Ix> where T: 'a, Ix: Index {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.next_back().map(|&index| &contents[index.to_usize()])
+    }
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        let contents = self.contents;
+        self.permutation.nth_back(n).map(|&index| &contents[index.to_usize()])
+    }
+}
+
+impl<'a, T, Ix> ExactSizeIterator for PresortedByKeyIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+impl<'a, T, Ix> FusedIterator for PresortedByKeyIter<'a, T, Ix> where T: 'a, Ix: Index {}
+
+/// A mutable reference to an element of a presorted vector sorted by key,
+/// which recomputes the key of the element when it is dropped.
+pub struct PresortedByKeyRefMut<'a, T, K, F, Ix = usize> where T: 'a, K: 'a + Ord, F: 'a + Fn(&T) -> K, Ix: 'a + Index {
+    // The vector being mutated
+    vec: &'a mut PresortedVecByKey<T, K, F, Ix>,
+    // The index of the element being mutated
+    index: usize,
+}
+
+impl<'a, T, K, F, Ix> Deref for PresortedByKeyRefMut<'a, T, K, F, Ix> where T: 'a, K: 'a + Ord, F: 'a + Fn(&T) -> K, Ix: 'a + Index {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.vec.contents[self.index]
+    }
+}
+
+impl<'a, T, K, F, Ix> DerefMut for PresortedByKeyRefMut<'a, T, K, F, Ix> where T: 'a, K: 'a + Ord, F: 'a + Fn(&T) -> K, Ix: 'a + Index {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.vec.contents[self.index]
+    }
+}
+
+impl<'a, T, K, F, Ix> Drop for PresortedByKeyRefMut<'a, T, K, F, Ix> where T: 'a, K: 'a + Ord, F: 'a + Fn(&T) -> K, Ix: 'a + Index {
+    fn drop(&mut self) {
+        // The reference was not leaked, so the key can be refreshed now
+        self.vec.stale.pop();
+        let key = (self.vec.key)(&self.vec.contents[self.index]);
+        self.vec.keys.set(self.index, key);
+    }
+}
+
+impl<T, K, F> PresortedVecByKey<T, K, F> where K: Ord, F: Fn(&T) -> K {
+    /// Create a new, empty vector, sorted by the key computed by `key`.
+    pub fn new(key: F) -> PresortedVecByKey<T, K, F> {
+        PresortedVecByKey::new_in(key)
+    }
+}
+
+impl<T, K, F, Ix> PresortedVecByKey<T, K, F, Ix> where K: Ord, F: Fn(&T) -> K, Ix: Index {
+    /// Create a new, empty vector, sorted by the key computed by `key`,
+    /// for any index type, for example `PresortedVecByKey::<T, K, F, u32>::new_in(key)`.
+    pub fn new_in(key: F) -> PresortedVecByKey<T, K, F, Ix> {
+        PresortedVecByKey {
+            contents: Vec::new(),
+            keys: PresortedVec::new_in(),
+            stale: Vec::new(),
+            key,
+        }
+    }
+
+    /// The length of the vector.
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    /// Append an element to the end of the vector.
+    pub fn push(&mut self, value: T) {
+        self.keys.push((self.key)(&value));
+        self.contents.push(value);
+    }
+
+    /// Set the `i`th element of the vector.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.keys.set(index, (self.key)(&value));
+        self.contents[index] = value;
+    }
+
+    /// Set many elements of the vector at once.
+    /// The key of each edited element is computed once per edit,
+    /// but the elements are not compared with their neighbours until the next sort.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        let contents = &mut self.contents;
+        let key = &self.key;
+        self.keys.apply_edits(edits.into_iter().map(|(index, value)| {
+            let value_key = key(&value);
+            contents[index] = value;
+            (index, value_key)
+        }));
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// The appended elements are not compared with their neighbours until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        let contents = &mut self.contents;
+        let key = &self.key;
+        self.keys.extend_edits(values.into_iter().map(|value| {
+            let value_key = key(&value);
+            contents.push(value);
+            value_key
+        }));
+    }
+
+    /// Truncate this vector.
+    /// This does not change whether the vector is sorted.
+    pub fn truncate(&mut self, len: usize) {
+        self.stale.retain(|&index| index < len);
+        self.keys.truncate(len);
+        self.contents.truncate(len);
+    }
+
+    /// Sort the permutation on the vector
+    pub fn sort(&mut self) {
+        for index in self.stale.drain(..) {
+            self.keys.set(index, (self.key)(&self.contents[index]));
+        }
+        self.keys.sort();
+    }
+
+    /// An iterator over the presorted vector
+    pub fn presorted_iter(&self) -> PresortedByKeyIter<'_, T, Ix> {
+        PresortedByKeyIter {
+            permutation: self.keys.permutation_iter(),
+            contents: &self.contents,
+        }
+    }
+
+    /// Is the presorted vector already sorted?
+    /// Elements whose mutable reference was leaked count as unsorted until the next sort.
+    pub fn is_sorted(&self) -> bool {
+        self.stale.is_empty() && self.keys.is_sorted()
+    }
+
+    /// A sorted iterator over the vector.
+    pub fn sorted_iter(&mut self) -> PresortedByKeyIter<'_, T, Ix> {
+        self.sort();
+        self.presorted_iter()
+    }
+
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> PermutedIndexedIter<'_, T, Ix> {
+        self.sort();
+        PermutedIndexedIter::new(&self.contents, self.keys.permutation_iter().as_slice())
+    }
+
+    /// Get the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.contents.get(index)
+    }
+
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// The key of the element is recomputed when the reference is dropped,
+    /// or at the next sort if the reference is leaked.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_mut(&mut self, index: usize) -> Option<PresortedByKeyRefMut<'_, T, K, F, Ix>> {
+        if index < self.len() {
+            // If the reference is leaked, the element is left displaced
+            self.keys.mark_displaced(index);
+            self.stale.push(index);
+            Some(PresortedByKeyRefMut { vec: self, index })
+        } else {
+            None
+        }
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    /// Comparisons are made between cached keys.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.keys.stats()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.keys.reset_stats();
+    }
+
+    /// Get the cached key of the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_key(&self, index: usize) -> Option<&K> {
+        self.keys.get(index)
+    }
+}
+
+#[cfg(feature = "heapsize")]
+impl<T, K, F, Ix> heapsize::HeapSizeOf for PresortedVecByKey<T, K, F, Ix>
+    where T: heapsize::HeapSizeOf, K: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
+{
+    fn heap_size_of_children(&self) -> usize {
+        self.contents.heap_size_of_children() + self.keys.heap_size_of_children() +
+            self.stale.heap_size_of_children()
+    }
+}
+
+#[test]
+fn test_push_set() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut vec = PresortedVecByKey::new(|value: &&str| { calls.set(calls.get() + 1); value.len() });
+    vec.push("a");
+    vec.push("ccc");
+    vec.push("bb");
+    assert_eq!(calls.get(), 3);
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get(2), Some(&"bb"));
+    assert_eq!(vec.get_key(2), Some(&2));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"a", &"bb", &"ccc"]);
+    assert_eq!(calls.get(), 3);
+
+    vec.set(0, "dddd");
+    assert_eq!(calls.get(), 4);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"bb", &"ccc", &"dddd"]);
+
+    vec.set(1, "eee");
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"bb", &"eee", &"dddd"]);
+    assert_eq!(calls.get(), 5);
+
+    vec.truncate(2);
+    assert_eq!(vec.len(), 2);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"eee", &"dddd"]);
+    assert_eq!(vec.sorted_iter().rev().collect::<Vec<&&str>>(), vec![&"dddd", &"eee"]);
+    assert_eq!(vec.sorted_iter().len(), 2);
+    assert_eq!(vec.sorted_indexed_iter().collect::<Vec<(usize, &&str)>>(), vec![(1, &"eee"), (0, &"dddd")]);
+    assert_eq!(calls.get(), 5);
+}
+
+#[test]
+fn test_apply_edits() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut vec = PresortedVecByKey::new(|value: &&str| { calls.set(calls.get() + 1); value.len() });
+    vec.extend_edits(vec!["a", "bb", "ccc"]);
+    vec.sort();
+    calls.set(0);
+
+    vec.apply_edits(vec![(0, "ffffff"), (1, "dd"), (0, "eeeee")]);
+    assert_eq!(calls.get(), 3);
+    assert_eq!(vec.get(0), Some(&"eeeee"));
+    assert_eq!(vec.get_key(0), Some(&5));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"dd", &"ccc", &"eeeee"]);
+    assert_eq!(calls.get(), 3);
+
+    vec.apply_edits(vec![(1, "d")]);
+    assert_eq!(vec.is_sorted(), true);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"d", &"ccc", &"eeeee"]);
+}
+
+#[test]
+fn test_extend_edits() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let mut vec = PresortedVecByKey::new(|value: &&str| { calls.set(calls.get() + 1); value.len() });
+    vec.push("bb");
+    vec.extend_edits(vec!["dddd", "a", "ccc"]);
+    assert_eq!(calls.get(), 4);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get_key(2), Some(&1));
+    assert_eq!(vec.sorted_iter().collect::<Vec<&&str>>(), vec![&"a", &"bb", &"ccc", &"dddd"]);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![2, 0, 3, 1]);
+    assert_eq!(calls.get(), 4);
+}
+
+#[test]
+fn test_get_mut() {
+    let mut vec = PresortedVecByKey::new(|value: &String| value.len());
+    vec.push(String::from("a"));
+    vec.push(String::from("ccc"));
+    vec.get_mut(0).unwrap().push_str("bb");
+    assert_eq!(vec.get_key(0), Some(&3));
+    assert_eq!(vec.is_sorted(), true);
+
+    vec.get_mut(1).unwrap().truncate(1);
+    assert_eq!(vec.get_key(1), Some(&1));
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&String>>(), vec!["c", "abb"]);
+    assert!(vec.get_mut(2).is_none());
+
+    let mut leaked = vec.get_mut(1).unwrap();
+    leaked.push_str("dddd");
+    ::std::mem::forget(leaked);
+    assert_eq!(vec.is_sorted(), false);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&String>>(), vec!["abb", "cdddd"]);
+    assert_eq!(vec.get_key(1), Some(&5));
+    assert_eq!(vec.is_sorted(), true);
+}
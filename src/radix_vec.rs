@@ -0,0 +1,298 @@
+use index::Index;
+use permuted_vec::PermutedIndexedIter;
+use presorted_vec::{PresortedIter, PresortedRefMut, PresortedVec};
+use compare::Natural;
+#[cfg(feature = "stats")]
+use stats::Stats;
+
+/// The trait of types with integer keys, which can be radix sorted.
+///
+/// The order on keys must agree with the `Ord` implementation,
+/// for example a type sorted by a z-index or a timestamp
+/// can use that as its key.
+///
+/// This is implemented by the unsigned integer types, whose key is the value,
+/// and by the signed integer types, whose key is the value with its sign bit flipped,
+/// so negative values come first.
+pub trait RadixKey: Ord {
+    /// The number of bytes of the key which are used.
+    const BYTES: usize;
+
+    /// The key, which must fit in `BYTES` bytes.
+    fn radix_key(&self) -> u64;
+}
+
+macro_rules! impl_radix_key {
+    ($type: ty) => {
+        impl RadixKey for $type {
+            const BYTES: usize = ::std::mem::size_of::<$type>();
+            fn radix_key(&self) -> u64 {
+                *self as u64
+            }
+        }
+    }
+}
+
+macro_rules! impl_signed_radix_key {
+    ($type: ty, $unsigned: ty) => {
+        impl RadixKey for $type {
+            const BYTES: usize = ::std::mem::size_of::<$type>();
+            fn radix_key(&self) -> u64 {
+                (*self as $unsigned ^ (1 << (8 * Self::BYTES - 1))) as u64
+            }
+        }
+    }
+}
+
+impl_radix_key!(u8);
+impl_radix_key!(u16);
+impl_radix_key!(u32);
+impl_radix_key!(u64);
+impl_radix_key!(usize);
+impl_signed_radix_key!(i8, u8);
+impl_signed_radix_key!(i16, u16);
+impl_signed_radix_key!(i32, u32);
+impl_signed_radix_key!(i64, u64);
+impl_signed_radix_key!(isize, usize);
+
+/// The type of presorted vectors of integer keys.
+///
+/// Like a `PresortedVec`, elements which are pushed or set in order with their
+/// neighbours do not need sorting, so if no element is displaced sorting takes O(1) time,
+/// and a few displaced elements are sorted and merged back into the permutation.
+/// Otherwise the permutation is rebuilt by an LSD radix sort of the keys,
+/// which takes O(n) time per byte of key.
+///
+/// The permutation is stored using the index type `Ix`, as in a `PresortedVec`.
+#[derive(Clone,Debug)]
+pub struct RadixPresortedVec<T, Ix = usize> {
+    // The underlying presorted vector
+    vec: PresortedVec<T, Natural, Ix>,
+}
+
+impl<T> RadixPresortedVec<T> where T: RadixKey {
+    /// Create a new, empty radix presorted vector.
+    pub fn new() -> RadixPresortedVec<T> {
+        RadixPresortedVec::new_in()
+    }
+}
+
+impl<T, Ix> RadixPresortedVec<T, Ix> where T: RadixKey, Ix: Index {
+    /// Create a new, empty radix presorted vector,
+    /// for any index type, for example `RadixPresortedVec::<T, u32>::new_in()`.
+    pub fn new_in() -> RadixPresortedVec<T, Ix> {
+        RadixPresortedVec {
+            vec: PresortedVec::new_in(),
+        }
+    }
+
+    /// The length of the vector.
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Is the vector empty?
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Append an element to the end of the vector.
+    pub fn push(&mut self, value: T) {
+        self.vec.push(value);
+    }
+
+    /// Set the `i`th element of the vector.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.vec.set(index, value);
+    }
+
+    /// Set many elements of the vector at once.
+    /// The edited elements are not compared with their neighbours until the next sort.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    pub fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.vec.apply_edits(edits);
+    }
+
+    /// Append many elements to the end of the vector at once.
+    /// The appended elements are not compared with their neighbours until the next sort.
+    pub fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.vec.extend_edits(values);
+    }
+
+    /// Truncate this vector.
+    /// This does not change whether the vector is sorted.
+    pub fn truncate(&mut self, len: usize) {
+        self.vec.truncate(len);
+    }
+
+    /// Sort the vector, by a radix sort if many elements are out of order.
+    pub fn sort(&mut self) {
+        self.vec.radix_sort_by_key(T::BYTES, T::radix_key);
+    }
+
+    /// Is the vector already sorted?
+    pub fn is_sorted(&self) -> bool {
+        self.vec.is_sorted()
+    }
+
+    /// An iterator over the presorted vector
+    pub fn presorted_iter(&self) -> PresortedIter<'_, T, Ix> {
+        self.vec.presorted_iter()
+    }
+
+    /// A sorted iterator over the vector.
+    pub fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
+        self.sort();
+        self.vec.presorted_iter()
+    }
+
+    /// A sorted iterator over the vector, together with the index of each element.
+    pub fn sorted_indexed_iter(&mut self) -> PermutedIndexedIter<'_, T, Ix> {
+        self.sort();
+        self.vec.sorted_indexed_iter()
+    }
+
+    /// Get the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.vec.get(index)
+    }
+
+    /// Get a mutable reference to the `i`th element of the vector.
+    /// Whether the vector is still sorted is checked when the reference is dropped.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    pub fn get_mut(&mut self, index: usize) -> Option<PresortedRefMut<'_, T, Natural, Ix>> {
+        self.vec.get_mut(index)
+    }
+
+    /// The rank of the `i`th element in sorted order.
+    /// Panics if the vector contains fewer than `i` elements.
+    pub fn rank_of(&mut self, index: usize) -> usize {
+        self.sort();
+        self.vec.rank_of(index)
+    }
+
+    /// The `k`th smallest element of the vector.
+    /// Returns `None` if the vector contains fewer than `k` elements.
+    pub fn nth_sorted(&mut self, k: usize) -> Option<&T> {
+        self.sort();
+        self.vec.nth_sorted(k)
+    }
+
+    /// The number of elements displaced since the last sort, which may be out of order.
+    pub fn displaced_count(&self) -> usize {
+        self.vec.displaced_count()
+    }
+
+    /// The work done by the vector since it was created or its stats were reset.
+    /// Radix sorts make no comparisons, but count as full sorts.
+    /// Repairs of a few displaced elements compare their keys.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.vec.stats()
+    }
+
+    /// Reset the counts of the work done by the vector.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.vec.reset_stats();
+    }
+}
+
+impl<T> From<Vec<T>> for RadixPresortedVec<T> where T: RadixKey {
+    fn from(vec: Vec<T>) -> RadixPresortedVec<T> {
+        RadixPresortedVec {
+            vec: PresortedVec::from(vec),
+        }
+    }
+}
+
+impl<T, Ix> Default for RadixPresortedVec<T, Ix> where T: RadixKey, Ix: Index {
+    fn default() -> RadixPresortedVec<T, Ix> {
+        RadixPresortedVec::new_in()
+    }
+}
+
+#[cfg(feature = "heapsize")]
+impl<T, Ix> heapsize::HeapSizeOf for RadixPresortedVec<T, Ix>
+    where T: heapsize::HeapSizeOf, Ix: heapsize::HeapSizeOf
+{
+    fn heap_size_of_children(&self) -> usize {
+        self.vec.heap_size_of_children()
+    }
+}
+
+#[test]
+fn test_radix_sort() {
+    let mut vec = RadixPresortedVec::from(vec![0x0300u32, 0x0102, 0x0201, 0x0102, 0x0101, 7]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&u32>>(), vec![&7, &0x0101, &0x0102, &0x0102, &0x0201, &0x0300]);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![5, 4, 1, 3, 2, 0]);
+    assert_eq!(vec.is_sorted(), true);
+
+    vec.set(0, 0x0400);
+    vec.push(0x0500);
+    assert_eq!(vec.displaced_count(), 0);
+    vec.set(5, 0x0250);
+    assert_eq!(vec.displaced_count(), 1);
+    assert_eq!(vec.rank_of(5), 4);
+    assert_eq!(vec.nth_sorted(0), Some(&0x0101));
+
+    vec.truncate(3);
+    vec.extend_edits(vec![u32::MAX, 0]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&u32>>(), vec![&0, &0x0102, &0x0201, &0x0400, &u32::MAX]);
+}
+
+#[test]
+fn test_radix_sort_random() {
+    let mut seed = 12345u64;
+    let mut values = Vec::new();
+    for _ in 0..1000 {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        values.push(seed >> 20);
+    }
+    let mut vec = RadixPresortedVec::from(values.clone());
+    values.sort();
+    assert_eq!(vec.sorted_iter().cloned().collect::<Vec<u64>>(), values);
+}
+
+#[test]
+fn test_radix_sort_signed() {
+    let mut vec = RadixPresortedVec::from(vec![3i32, -1, i32::MIN, 0, i32::MAX, -100]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&i32>>(), vec![&i32::MIN, &-100, &-1, &0, &3, &i32::MAX]);
+
+    let mut vec = RadixPresortedVec::from(vec![1i8, -128, 127, -1, 0]);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&i8>>(), vec![&-128, &-1, &0, &1, &127]);
+}
+
+#[test]
+fn test_index_type() {
+    let mut vec = RadixPresortedVec::<u64, u16>::new_in();
+    vec.extend_edits(vec![3, 1, 2, 1]);
+    assert_eq!(vec.sorted_indexed_iter().map(|(index, _)| index).collect::<Vec<usize>>(), vec![1, 3, 2, 0]);
+    assert_eq!(vec.rank_of(2), 2);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats() {
+    let mut vec = RadixPresortedVec::from(vec![30u16, 10, 20]);
+    vec.sort();
+    assert_eq!(vec.stats().full_sorts, 1);
+    vec.reset_stats();
+    vec.set(1, 25);
+    vec.sort();
+    assert_eq!(vec.stats(), Stats { comparisons: 4, skipped_sorts: 0, unsorted: 0, full_sorts: 0 });
+
+    // One displaced element of many is repaired rather than radix sorted
+    let mut vec = RadixPresortedVec::from((0..100u16).collect::<Vec<u16>>());
+    vec.sort();
+    vec.reset_stats();
+    vec.set(50, 5);
+    vec.sort();
+    assert_eq!(vec.stats().full_sorts, 0);
+    assert_eq!(vec.nth_sorted(6), Some(&5));
+    vec.apply_edits((0..50).map(|index| (index, 100 - index as u16)));
+    vec.sort();
+    assert_eq!(vec.stats().full_sorts, 1);
+}
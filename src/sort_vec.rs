@@ -0,0 +1,372 @@
+use std::slice;
+
+use compare::Compare;
+use index::Index;
+use permuted_vec::{PermutedIter, PermutedVec};
+use presorted_vec::{PresortedIter, PresortedVec};
+use presorted_vec_by_key::{PresortedByKeyIter, PresortedVecByKey};
+use inline_key_vec::{InlineKeyIter, InlineKeyVec};
+use merge_vec::{MergeVecIter, MergeVec};
+use adaptive_vec::AdaptiveSortVec;
+use radix_vec::{RadixKey, RadixPresortedVec};
+
+/// The trait of vectors which can be incrementally sorted.
+///
+/// This is implemented by `PermutedVec`, `PresortedVec`, `MergeVec` and `AdaptiveSortVec`,
+/// so code can be written generically and the sorting strategy chosen by a
+/// type parameter. It is also implemented by `Vec`, which sorts in place,
+/// as a baseline for comparison.
+pub trait SortVec<T> {
+    /// The type of sorted iterators over the vector.
+    type SortedIter<'a>: Iterator<Item=&'a T> where Self: 'a, T: 'a;
+
+    /// The length of the vector.
+    fn len(&self) -> usize;
+
+    /// Is the vector empty?
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the `i`th element of the vector.
+    /// Returns `None` if the vector contains fewer than `i` elements.
+    fn get(&self, index: usize) -> Option<&T>;
+
+    /// Append an element to the end of the vector.
+    fn push(&mut self, value: T);
+
+    /// Set the `i`th element of the vector.
+    /// Panics if the vector contains fewer than `i` elements.
+    fn set(&mut self, index: usize, value: T);
+
+    /// Set many elements of the vector at once,
+    /// leaving any checks of whether the vector is still sorted until the next sort.
+    /// Panics if the vector contains fewer than `i` elements for an edit of index `i`.
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        for (index, value) in edits {
+            self.set(index, value);
+        }
+    }
+
+    /// Append many elements to the end of the vector at once,
+    /// leaving any checks of whether the vector is still sorted until the next sort.
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        for value in values {
+            self.push(value);
+        }
+    }
+
+    /// Truncate this vector and reset the sort if necessary.
+    fn truncate(&mut self, len: usize);
+
+    /// Sort the vector.
+    fn sort(&mut self);
+
+    /// A sorted iterator over the vector.
+    fn sorted_iter(&mut self) -> Self::SortedIter<'_>;
+}
+
+impl<T> SortVec<T> for Vec<T> where T: Ord {
+    type SortedIter<'a> = slice::Iter<'a, T> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self[..].get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self[index] = value;
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self[..].sort();
+    }
+    fn sorted_iter(&mut self) -> slice::Iter<'_, T> {
+        self[..].sort();
+        self.iter()
+    }
+}
+
+impl<T, Ix> SortVec<T> for PermutedVec<T, Ix> where T: Ord, Ix: Index {
+    type SortedIter<'a> = PermutedIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.resort_by(T::cmp);
+    }
+    fn sorted_iter(&mut self) -> PermutedIter<'_, T, Ix> {
+        self.resort_by(T::cmp);
+        self.permuted_iter()
+    }
+}
+
+impl<T, C, Ix> SortVec<T> for PresortedVec<T, C, Ix> where C: Compare<T>, Ix: Index {
+    type SortedIter<'a> = PresortedIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
+        self.sorted_iter()
+    }
+}
+
+impl<T, K, F, Ix> SortVec<T> for PresortedVecByKey<T, K, F, Ix> where K: Ord, F: Fn(&T) -> K, Ix: Index {
+    type SortedIter<'a> = PresortedByKeyIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> PresortedByKeyIter<'_, T, Ix> {
+        self.sorted_iter()
+    }
+}
+
+impl<T, K, F, Ix> SortVec<T> for InlineKeyVec<T, K, F, Ix> where K: Ord + Copy, F: Fn(&T) -> K, Ix: Index {
+    type SortedIter<'a> = InlineKeyIter<'a, T, K, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> InlineKeyIter<'_, T, K, Ix> {
+        self.sorted_iter()
+    }
+}
+
+impl<T, Ix> SortVec<T> for MergeVec<T, Ix> where T: Ord, Ix: Index {
+    type SortedIter<'a> = MergeVecIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> MergeVecIter<'_, T, Ix> {
+        self.sorted_iter()
+    }
+}
+
+impl<T, Ix> SortVec<T> for AdaptiveSortVec<T, Ix> where T: Ord, Ix: Index {
+    type SortedIter<'a> = PresortedIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
+        self.sorted_iter()
+    }
+}
+
+impl<T, Ix> SortVec<T> for RadixPresortedVec<T, Ix> where T: RadixKey, Ix: Index {
+    type SortedIter<'a> = PresortedIter<'a, T, Ix> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn get(&self, index: usize) -> Option<&T> {
+        self.get(index)
+    }
+    fn push(&mut self, value: T) {
+        self.push(value);
+    }
+    fn set(&mut self, index: usize, value: T) {
+        self.set(index, value);
+    }
+    fn apply_edits<I>(&mut self, edits: I) where I: IntoIterator<Item=(usize, T)> {
+        self.apply_edits(edits);
+    }
+    fn extend_edits<I>(&mut self, values: I) where I: IntoIterator<Item=T> {
+        self.extend_edits(values);
+    }
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+    fn sort(&mut self) {
+        self.sort();
+    }
+    fn sorted_iter(&mut self) -> PresortedIter<'_, T, Ix> {
+        self.sorted_iter()
+    }
+}
+
+#[cfg(test)]
+fn check_sort_vec<V>(mut vec: V) where V: SortVec<usize> {
+    assert_eq!(vec.len(), 0);
+    assert_eq!(vec.is_empty(), true);
+    vec.push(0);
+    vec.push(30);
+    vec.push(20);
+    vec.push(10);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &10, &20, &30]);
+    vec.set(3, 40);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.get(3), Some(&40));
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &20, &30, &40]);
+    vec.truncate(2);
+    assert_eq!(vec.len(), 2);
+    assert_eq!(vec.get(2), None);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&0, &30]);
+    vec.apply_edits(vec![(0, 50), (1, 10)]);
+    vec.extend_edits(vec![20, 60]);
+    assert_eq!(vec.len(), 4);
+    assert_eq!(vec.sorted_iter().collect::<Vec<&usize>>(), vec![&10, &20, &50, &60]);
+}
+
+#[test]
+fn test_sort_vec() {
+    check_sort_vec(PermutedVec::new());
+    check_sort_vec(PresortedVec::new());
+    check_sort_vec(PresortedVecByKey::new(|&value: &usize| value));
+    check_sort_vec(InlineKeyVec::new(|&value: &usize| value));
+    check_sort_vec(MergeVec::new());
+    check_sort_vec(AdaptiveSortVec::new());
+    check_sort_vec(RadixPresortedVec::new());
+}
+
+#[test]
+fn test_sort_vec_index() {
+    use compare::Natural;
+
+    check_sort_vec(PermutedVec::<usize, u16>::new_in());
+    check_sort_vec(PermutedVec::<usize, u32>::with_capacity_in(10));
+    check_sort_vec(PresortedVec::<usize, Natural, u32>::new_in());
+    check_sort_vec(PresortedVec::<usize, Natural, u16>::with_capacity_in(10));
+    check_sort_vec(PresortedVec::<usize, _, u16>::new_by_in(|value_1: &usize, value_2: &usize| value_1.cmp(value_2)));
+    check_sort_vec(PresortedVec::<usize, _, u32>::new_by_key_in(|value: &usize| *value));
+    check_sort_vec(PresortedVecByKey::<usize, usize, fn(&usize) -> usize, u16>::new_in(|value| *value));
+    check_sort_vec(MergeVec::<usize, u16>::new_in());
+    check_sort_vec(MergeVec::<usize, u16>::with_capacity_in(10));
+    check_sort_vec(MergeVec::<usize, u32>::new_stable_in());
+    check_sort_vec(InlineKeyVec::<usize, usize, fn(&usize) -> usize, u32>::new_in(|value| *value));
+    check_sort_vec(AdaptiveSortVec::<usize, u32>::new_in());
+    check_sort_vec(RadixPresortedVec::<usize, u16>::new_in());
+}
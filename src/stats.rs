@@ -0,0 +1,92 @@
+//! Instrumentation of the work done by vectors.
+//!
+//! When the `stats` feature is enabled, each vector counts the work it does,
+//! which can be read with `stats()` and cleared with `reset_stats()`.
+//! Without the feature, nothing is counted and the counters take no space.
+
+#[cfg(feature = "stats")]
+use std::cell::Cell;
+use std::ops::Add;
+
+/// A snapshot of the work done by a vector.
+#[derive(Clone,Copy,Debug,Default,Eq,PartialEq)]
+pub struct Stats {
+    /// The number of comparisons between elements made while updating or sorting.
+    pub comparisons: usize,
+    /// The number of sorts skipped because the vector was already sorted.
+    pub skipped_sorts: usize,
+    /// The number of elements routed to the unsorted list of a merge vector.
+    pub unsorted: usize,
+    /// The number of sorts which sorted every element.
+    pub full_sorts: usize,
+}
+
+impl Add for Stats {
+    type Output = Stats;
+    fn add(self, other: Stats) -> Stats {
+        Stats {
+            comparisons: self.comparisons + other.comparisons,
+            skipped_sorts: self.skipped_sorts + other.skipped_sorts,
+            unsorted: self.unsorted + other.unsorted,
+            full_sorts: self.full_sorts + other.full_sorts,
+        }
+    }
+}
+
+// The counters kept by a vector, which are updated through a shared reference,
+// so comparisons can be counted by methods which do not mutate the vector.
+// Counters do not affect equality of vectors.
+#[derive(Clone,Debug,Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "stats")]
+    stats: Cell<Stats>,
+}
+
+#[cfg(feature = "stats")]
+impl Counters {
+    fn update<F>(&self, f: F) where F: FnOnce(&mut Stats) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    pub(crate) fn get(&self) -> Stats {
+        self.stats.get()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.stats.set(Stats::default());
+    }
+
+    pub(crate) fn compared(&self, count: usize) {
+        self.update(|stats| stats.comparisons += count);
+    }
+
+    pub(crate) fn skipped_sort(&self) {
+        self.update(|stats| stats.skipped_sorts += 1);
+    }
+
+    pub(crate) fn unsorted(&self, count: usize) {
+        self.update(|stats| stats.unsorted += count);
+    }
+
+    pub(crate) fn full_sort(&self) {
+        self.update(|stats| stats.full_sorts += 1);
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Counters {
+    pub(crate) fn compared(&self, _count: usize) {}
+    pub(crate) fn skipped_sort(&self) {}
+    pub(crate) fn unsorted(&self, _count: usize) {}
+    pub(crate) fn full_sort(&self) {}
+}
+
+impl PartialEq for Counters {
+    fn eq(&self, _other: &Counters) -> bool {
+        true
+    }
+}
+
+impl Eq for Counters {}